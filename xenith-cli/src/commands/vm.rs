@@ -16,7 +16,82 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use clap::{Args, Subcommand};
+use clap::{Args, Subcommand, ValueEnum};
+use thiserror::Error;
+use xenith_vm::XlConfiguration;
+use xenith_vm::domain::{Domain, FieldChange, list_host_bridges};
+use xenith_vm::error::TemplateError;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Default directory where domain configurations are stored
+pub const DEFAULT_CONFIG_DIRECTORY: &str = "/etc/xenith/domains";
+
+/// Name of the portable domain description stored in each domain directory
+const DOMAIN_DESCRIPTION_FILE: &str = "domain.json";
+
+/// Name of the working copy of the domain description while it is edited
+const DOMAIN_EDIT_FILE: &str = "domain.json.edit";
+
+/// Errors that can occur while handling VM commands
+#[derive(Debug, Error)]
+pub enum VmError {
+    #[error("Invalid domain name: \"{0}\"")]
+    InvalidName(String),
+    #[error("Domain {0} already exists")]
+    AlreadyExists(String),
+    #[error("Domain {0} not found")]
+    NotFound(String),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("TOML serialization error: {0}")]
+    TomlSerialize(#[from] toml::ser::Error),
+    #[error("TOML deserialization error: {0}")]
+    TomlDeserialize(#[from] toml::de::Error),
+    #[error("Template error: {0}")]
+    Template(#[from] TemplateError),
+    #[error("Editor failed: {0}")]
+    Editor(String),
+    #[error("Changes to domain {0} were not applied")]
+    Declined(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Formats of a portable domain description
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DomainFormat {
+    #[default]
+    Json,
+    Toml,
+}
+
+impl DomainFormat {
+    /// Serialize a domain into this format
+    ///
+    /// # Arguments
+    ///
+    /// * `domain` - The domain to serialize
+    pub fn serialize(&self, domain: &Domain) -> Result<String, VmError> {
+        Ok(match self {
+            DomainFormat::Json => serde_json::to_string_pretty(domain)?,
+            DomainFormat::Toml => toml::to_string_pretty(domain)?,
+        })
+    }
+
+    /// Deserialize a domain from this format
+    ///
+    /// # Arguments
+    ///
+    /// * `description` - The serialized domain
+    pub fn deserialize(&self, description: &str) -> Result<Domain, VmError> {
+        Ok(match self {
+            DomainFormat::Json => serde_json::from_str(description)?,
+            DomainFormat::Toml => toml::from_str(description)?,
+        })
+    }
+}
 
 #[derive(Debug, Args)]
 #[command(args_conflicts_with_subcommands = true)]
@@ -29,6 +104,14 @@ pub struct VmArgs {
 #[derive(Debug, Subcommand)]
 pub enum VmCommands {
     Create(VmCreateArgs),
+    #[command(about = "Export a domain to a portable description")]
+    Export(VmExportArgs),
+    #[command(about = "Import a domain from a portable description")]
+    Import(VmImportArgs),
+    #[command(about = "Edit a domain description in $EDITOR")]
+    Edit(VmEditArgs),
+    #[command(about = "Print the xl configuration rendered for a domain")]
+    Render(VmRenderArgs),
     Destroy,
     Up,
     Halt,
@@ -38,13 +121,335 @@ pub enum VmCommands {
 pub struct VmCreateArgs {
     #[arg(short, long)]
     test: Option<String>,
+    /// Host bridge the network interface of the domain is added to
+    #[arg(long)]
+    bridge: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct VmExportArgs {
+    /// Name of the domain to export
+    name: String,
+    /// Format of the exported description
+    #[arg(short, long, value_enum, default_value_t)]
+    format: DomainFormat,
+    /// Directory where domain configurations are stored
+    #[arg(long, default_value = DEFAULT_CONFIG_DIRECTORY)]
+    config_dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct VmImportArgs {
+    /// Path to the domain description to import
+    file: PathBuf,
+    /// Format of the imported description
+    #[arg(short, long, value_enum, default_value_t)]
+    format: DomainFormat,
+    /// Directory where domain configurations are stored
+    #[arg(long, default_value = DEFAULT_CONFIG_DIRECTORY)]
+    config_dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct VmEditArgs {
+    /// Name of the domain to edit
+    name: String,
+    /// Directory where domain configurations are stored
+    #[arg(long, default_value = DEFAULT_CONFIG_DIRECTORY)]
+    config_dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct VmRenderArgs {
+    /// Name of the domain to render
+    name: String,
+    /// File to write the configuration to, instead of printing it
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Directory where domain configurations are stored
+    #[arg(long, default_value = DEFAULT_CONFIG_DIRECTORY)]
+    config_dir: PathBuf,
+}
+
+/// Get the configuration directory of a domain
+///
+/// # Errors
+///
+/// Returns [`VmError::InvalidName`] if the name can't be used as a directory name
+fn domain_directory(config_dir: &Path, name: &str) -> Result<PathBuf, VmError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return Err(VmError::InvalidName(name.to_string()));
+    }
+
+    Ok(config_dir.join(name))
+}
+
+/// Export a stored domain to a portable description
+///
+/// # Arguments
+///
+/// * `config_dir` - Directory where domain configurations are stored
+/// * `name` - Name of the domain to export
+/// * `format` - Format of the exported description
+///
+/// # Returns
+///
+/// The serialized domain
+pub fn export_domain(
+    config_dir: &Path,
+    name: &str,
+    format: DomainFormat,
+) -> Result<String, VmError> {
+    format.serialize(&load_domain(config_dir, name)?)
+}
+
+/// Load a stored domain description
+///
+/// # Arguments
+///
+/// * `config_dir` - Directory where domain configurations are stored
+/// * `name` - Name of the domain to load
+///
+/// # Returns
+///
+/// The stored domain
+fn load_domain(config_dir: &Path, name: &str) -> Result<Domain, VmError> {
+    let description = domain_directory(config_dir, name)?.join(DOMAIN_DESCRIPTION_FILE);
+    if !description.is_file() {
+        return Err(VmError::NotFound(name.to_string()));
+    }
+
+    Ok(serde_json::from_str(&fs::read_to_string(description)?)?)
+}
+
+/// Render the xl configuration of a stored domain, without creating it
+///
+/// # Arguments
+///
+/// * `config_dir` - Directory where domain configurations are stored
+/// * `name` - Name of the domain to render
+///
+/// # Returns
+///
+/// The rendered `xl` configuration
+pub fn render_domain(config_dir: &Path, name: &str) -> Result<String, VmError> {
+    let domain = load_domain(config_dir, name)?;
+    domain.validate(false)?;
+
+    Ok(domain.xl_config())
+}
+
+/// Import a domain from a portable description
+///
+/// Disks without a virtual device get a default one and the domain is validated, then its
+/// directory is created with the portable description and the rendered `xl` configuration.
+///
+/// # Arguments
+///
+/// * `config_dir` - Directory where domain configurations are stored
+/// * `file` - Path to the domain description
+/// * `format` - Format of the description
+///
+/// # Returns
+///
+/// The directory of the imported domain
+pub fn import_domain(
+    config_dir: &Path,
+    file: &Path,
+    format: DomainFormat,
+) -> Result<PathBuf, VmError> {
+    let mut domain = format.deserialize(&fs::read_to_string(file)?)?;
+    let directory = domain_directory(config_dir, &domain.name.0)?;
+    if directory.exists() {
+        return Err(VmError::AlreadyExists(domain.name.0));
+    }
+
+    domain.disks.assign_default_vdevs();
+    domain.validate(false)?;
+
+    fs::create_dir_all(&directory)?;
+    fs::write(
+        directory.join(DOMAIN_DESCRIPTION_FILE),
+        serde_json::to_string_pretty(&domain)?,
+    )?;
+    fs::write(
+        directory.join(format!("{}.cfg", domain.name.0)),
+        domain.xl_config(),
+    )?;
+
+    Ok(directory)
+}
+
+/// Get the editor command, from `$EDITOR` or the platform default
+fn editor() -> String {
+    std::env::var("EDITOR")
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        })
+}
+
+/// Edit a stored domain description
+///
+/// The description is edited in a working copy, which is parsed and validated once the
+/// editor exits. The stored description and the rendered `xl` configuration are only
+/// replaced if the working copy is valid and its changes are approved, otherwise an invalid
+/// working copy is kept so that the next edit resumes from it, and a declined one is removed.
+///
+/// # Arguments
+///
+/// * `config_dir` - Directory where domain configurations are stored
+/// * `name` - Name of the domain to edit
+/// * `editor` - Editor command, the path of the working copy is appended to its arguments
+/// * `approve` - Called with the changes of a valid working copy, see [`Domain::diff`],
+///   returns whether they are applied
+///
+/// # Returns
+///
+/// The edited domain
+pub fn edit_domain(
+    config_dir: &Path,
+    name: &str,
+    editor: &str,
+    approve: impl FnOnce(&[FieldChange]) -> bool,
+) -> Result<Domain, VmError> {
+    let directory = domain_directory(config_dir, name)?;
+    let description = directory.join(DOMAIN_DESCRIPTION_FILE);
+    if !description.is_file() {
+        return Err(VmError::NotFound(name.to_string()));
+    }
+
+    let working_copy = directory.join(DOMAIN_EDIT_FILE);
+    if !working_copy.is_file() {
+        fs::copy(&description, &working_copy)?;
+    }
+
+    let mut arguments = editor.split_whitespace();
+    let program = arguments
+        .next()
+        .ok_or_else(|| VmError::Editor("no editor command".to_string()))?;
+    let status = std::process::Command::new(program)
+        .args(arguments)
+        .arg(&working_copy)
+        .status()?;
+    if !status.success() {
+        return Err(VmError::Editor(format!(
+            "{} exited with {}",
+            program, status
+        )));
+    }
+
+    let domain: Domain = serde_json::from_str(&fs::read_to_string(&working_copy)?)?;
+    if domain.name.0 != name {
+        return Err(VmError::InvalidName(domain.name.0));
+    }
+    domain.validate(false)?;
+
+    let stored: Domain = serde_json::from_str(&fs::read_to_string(&description)?)?;
+    if !approve(&stored.diff(&domain)) {
+        fs::remove_file(&working_copy)?;
+        return Err(VmError::Declined(name.to_string()));
+    }
+
+    fs::write(
+        directory.join(format!("{}.cfg", domain.name.0)),
+        domain.xl_config(),
+    )?;
+    fs::rename(&working_copy, &description)?;
+
+    Ok(domain)
+}
+
+/// Ask a yes/no question on the terminal, defaulting to yes
+fn confirm(question: &str) -> bool {
+    print!("{} [Y/n] ", question);
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    !matches!(answer.trim().to_lowercase().as_str(), "n" | "no")
 }
 
 pub fn handle(args: VmArgs) {
     match args.command {
         VmCommands::Create(create) => {
             log::info!("Creating VM with message: {:?}", create.test);
+            if let Some(bridge) = &create.bridge {
+                match list_host_bridges() {
+                    Ok(bridges) if !bridges.is_empty() && !bridges.contains(bridge) => {
+                        log::error!(
+                            "Bridge {} does not exist, available bridges: {}",
+                            bridge,
+                            bridges.join(", ")
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Failed to check bridge {}: {}", bridge, e),
+                }
+            }
         }
+        VmCommands::Export(export) => {
+            match export_domain(&export.config_dir, &export.name, export.format) {
+                Ok(description) => println!("{}", description),
+                Err(e) => log::error!("Failed to export domain {}: {}", export.name, e),
+            }
+        }
+        VmCommands::Import(import) => {
+            match import_domain(&import.config_dir, &import.file, import.format) {
+                Ok(directory) => log::info!("Imported domain into {}", directory.display()),
+                Err(e) => log::error!("Failed to import {}: {}", import.file.display(), e),
+            }
+        }
+        VmCommands::Edit(edit) => loop {
+            let approve = |changes: &[FieldChange]| {
+                if changes.is_empty() {
+                    log::info!("No change to the xl configuration");
+                    return true;
+                }
+                for change in changes {
+                    println!("{}", change);
+                }
+                confirm("Apply these changes?")
+            };
+            match edit_domain(&edit.config_dir, &edit.name, &editor(), approve) {
+                Ok(_) => {
+                    log::info!("Domain {} updated", edit.name);
+                    break;
+                }
+                Err(VmError::Declined(_)) => {
+                    log::info!("Domain {} left unchanged", edit.name);
+                    break;
+                }
+                Err(e @ (VmError::InvalidName(_) | VmError::Json(_) | VmError::Template(_))) => {
+                    log::error!("Invalid domain description: {}", e);
+                    if !confirm("Edit the description again?") {
+                        log::info!("Domain {} left unchanged", edit.name);
+                        break;
+                    }
+                }
+                Err(e) => {
+                    log::error!("Failed to edit domain {}: {}", edit.name, e);
+                    break;
+                }
+            }
+        },
+        VmCommands::Render(render) => match render_domain(&render.config_dir, &render.name) {
+            Ok(config) => match &render.output {
+                Some(output) => match fs::write(output, config) {
+                    Ok(()) => log::info!("Configuration written to {}", output.display()),
+                    Err(e) => log::error!("Failed to write {}: {}", output.display(), e),
+                },
+                None => print!("{}", config),
+            },
+            Err(e) => log::error!("Failed to render domain {}: {}", render.name, e),
+        },
         VmCommands::Destroy => {
             println!("Destroying VM");
         }
@@ -56,3 +461,152 @@ pub fn handle(args: VmArgs) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xenith_vm::domain::{
+        DomainName, GuestConsole, MaximumMemoryCapacity, MemoryCapacity, NetworkInterface,
+        NetworkInterfaces,
+    };
+
+    fn test_domain() -> Domain {
+        let mut domain = Domain {
+            name: DomainName("test".to_string()),
+            memory: MemoryCapacity(2048),
+            maximum_memory: MaximumMemoryCapacity(4096),
+            console: Some(GuestConsole::Spice {
+                port: Some(5900),
+                tls_port: None,
+                password: None,
+                disable_ticketing: true,
+            }),
+            network_interfaces: NetworkInterfaces(vec![NetworkInterface::default()]),
+            ..Default::default()
+        };
+        domain.set_label("environment", "test");
+        domain
+    }
+
+    #[test]
+    fn test_import_export_round_trip() {
+        for format in [DomainFormat::Json, DomainFormat::Toml] {
+            let config_dir = tempfile::tempdir().unwrap();
+            let file = config_dir.path().join("description");
+            let domain = test_domain();
+            fs::write(&file, format.serialize(&domain).unwrap()).unwrap();
+
+            let directory = import_domain(config_dir.path(), &file, format).unwrap();
+            assert_eq!(directory, config_dir.path().join("test"));
+            assert!(directory.join("test.cfg").is_file());
+
+            let exported = export_domain(config_dir.path(), "test", format).unwrap();
+            assert_eq!(format.deserialize(&exported).unwrap(), domain);
+
+            assert!(matches!(
+                import_domain(config_dir.path(), &file, format),
+                Err(VmError::AlreadyExists(_))
+            ));
+        }
+    }
+
+    /// Create an editor script running a sed expression on the edited file
+    #[cfg(unix)]
+    fn sed_editor(directory: &Path, expression: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let editor = directory.join(format!("editor-{}", expression.len()));
+        fs::write(
+            &editor,
+            format!("#!/bin/sh\nsed -i '{}' \"$1\"\n", expression),
+        )
+        .unwrap();
+        fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+        editor.display().to_string()
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_edit_domain() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let file = config_dir.path().join("description");
+        fs::write(&file, DomainFormat::Json.serialize(&test_domain()).unwrap()).unwrap();
+        let directory = import_domain(config_dir.path(), &file, DomainFormat::Json).unwrap();
+        let description = directory.join(DOMAIN_DESCRIPTION_FILE);
+        let original = fs::read_to_string(&description).unwrap();
+
+        // An invalid description is rejected and the stored one is kept
+        let invalid = sed_editor(config_dir.path(), r#"s/"memory": 2048/"memory": "2G"/"#);
+        assert!(matches!(
+            edit_domain(config_dir.path(), "test", &invalid, |_| true),
+            Err(VmError::Json(_))
+        ));
+        assert_eq!(fs::read_to_string(&description).unwrap(), original);
+        assert!(directory.join(DOMAIN_EDIT_FILE).is_file());
+
+        // The next edit resumes from the working copy, declined changes are discarded
+        let valid = sed_editor(config_dir.path(), r#"s/"memory": "2G"/"memory": 1024/"#);
+        let mut fields = Vec::new();
+        assert!(matches!(
+            edit_domain(config_dir.path(), "test", &valid, |changes| {
+                fields.extend(changes.iter().map(|change| change.field));
+                false
+            }),
+            Err(VmError::Declined(_))
+        ));
+        assert_eq!(fields, ["memory"]);
+        assert_eq!(fs::read_to_string(&description).unwrap(), original);
+        assert!(!directory.join(DOMAIN_EDIT_FILE).exists());
+
+        let valid = sed_editor(config_dir.path(), r#"s/"memory": 2048/"memory": 1024/"#);
+        let domain = edit_domain(config_dir.path(), "test", &valid, |_| true).unwrap();
+        assert_eq!(domain.memory, MemoryCapacity(1024));
+        assert!(!directory.join(DOMAIN_EDIT_FILE).exists());
+        assert_eq!(
+            fs::read_to_string(directory.join("test.cfg")).unwrap(),
+            domain.xl_config()
+        );
+
+        assert!(matches!(
+            edit_domain(config_dir.path(), "test", "false", |_| true),
+            Err(VmError::Editor(_))
+        ));
+        assert!(matches!(
+            edit_domain(config_dir.path(), "unknown", &valid, |_| true),
+            Err(VmError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_render_domain() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let file = config_dir.path().join("description");
+        fs::write(&file, DomainFormat::Json.serialize(&test_domain()).unwrap()).unwrap();
+        import_domain(config_dir.path(), &file, DomainFormat::Json).unwrap();
+
+        let rendered = render_domain(config_dir.path(), "test").unwrap();
+        assert!(rendered.contains("name = \"test\"\n"));
+        assert!(rendered.contains("memory = 2048 # in MB\n"));
+        assert!(rendered.contains("maxmem = 4096 # in MB\n"));
+        assert!(rendered.contains("vif = [ "));
+        assert!(rendered.contains("spice = 1\n"));
+
+        assert!(matches!(
+            render_domain(config_dir.path(), "unknown"),
+            Err(VmError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_export_unknown_domain() {
+        let config_dir = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            export_domain(config_dir.path(), "unknown", DomainFormat::Json),
+            Err(VmError::NotFound(_))
+        ));
+        assert!(matches!(
+            export_domain(config_dir.path(), "../test", DomainFormat::Json),
+            Err(VmError::InvalidName(_))
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+/*
+Xenith - Xen-based security hypervisor
+Copyright (C) 2025 Xenith contributors
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Clock sources
+//!
+//! Timing techniques read the time through a [`ClockSource`] instead of the system clock
+//! directly, so that their heuristics can be tested deterministically with a [`MockClock`]
+//! replaying VM-like or bare-metal-like timestamps.
+
+use std::cell::Cell;
+use std::time::Instant;
+
+/// A source of monotonic timestamps
+pub trait ClockSource {
+    /// Get the current time in nanoseconds, from an arbitrary origin
+    fn now_ns(&self) -> u64;
+}
+
+/// Monotonic clock of the system, the origin is the creation of the clock
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    /// Create a new system clock
+    pub fn new() -> Self {
+        SystemClock {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+impl ClockSource for SystemClock {
+    fn now_ns(&self) -> u64 {
+        self.origin.elapsed().as_nanos() as u64
+    }
+}
+
+/// Clock replaying a list of timestamps, one per read
+///
+/// Once all timestamps have been read, the last one is returned again, as a stopped clock.
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    timestamps: Vec<u64>,
+    next: Cell<usize>,
+}
+
+impl MockClock {
+    /// Create a clock replaying the given timestamps
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamps` - The timestamps in nanoseconds, in read order
+    pub fn new(timestamps: Vec<u64>) -> Self {
+        MockClock {
+            timestamps,
+            next: Cell::new(0),
+        }
+    }
+
+    /// Create a clock whose successive reads are separated by the given intervals
+    ///
+    /// The first read returns 0, the second one the first interval, and so on.
+    ///
+    /// # Arguments
+    ///
+    /// * `intervals` - The intervals between successive reads in nanoseconds
+    pub fn from_intervals(intervals: &[u64]) -> Self {
+        let timestamps = std::iter::once(0)
+            .chain(intervals.iter().scan(0, |now, interval| {
+                *now += interval;
+                Some(*now)
+            }))
+            .collect();
+        MockClock::new(timestamps)
+    }
+}
+
+impl ClockSource for MockClock {
+    fn now_ns(&self) -> u64 {
+        let next = self.next.get();
+        self.next.set(next + 1);
+        self.timestamps
+            .get(next)
+            .or(self.timestamps.last())
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_clock_is_monotonic() {
+        let clock = SystemClock::new();
+        let first = clock.now_ns();
+        assert!(clock.now_ns() >= first);
+    }
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::from_intervals(&[10, 5]);
+        assert_eq!(clock.now_ns(), 0);
+        assert_eq!(clock.now_ns(), 10);
+        assert_eq!(clock.now_ns(), 15);
+        // Stopped once all timestamps have been read
+        assert_eq!(clock.now_ns(), 15);
+
+        assert_eq!(MockClock::default().now_ns(), 0);
+    }
+}
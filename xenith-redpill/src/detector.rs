@@ -25,22 +25,34 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 //!
 //! The [`TechniqueRegistry`] struct is used to store a list of techniques and provides functions to register and run techniques.
 //!
+//! Techniques can also be loaded at runtime from a directory of descriptor files (see
+//! [`DynamicTechnique`]), to try new detections without recompiling.
+//!
 //! # Example
 //!
 //! To-do
 
-use std::error::Error;
+use std::cell::Cell;
 use std::fmt::Debug;
-use std::sync::Mutex;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, MutexGuard};
 
-use log::debug;
+use log::{debug, warn};
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Singleton global technique registry, used to store all registered techniques
 static TECHNIQUE_REGISTRY: Lazy<Mutex<TechniqueRegistry>> =
     Lazy::new(|| Mutex::new(TechniqueRegistry::new()));
 
+thread_local! {
+    /// Whether the current thread holds the lock of the global registry
+    static REGISTRY_HELD: Cell<bool> = const { Cell::new(false) };
+}
+
 /// The result of a detection technique
 pub type TechniqueResult = Result<DetectionResult, TechniqueError>;
 
@@ -53,6 +65,22 @@ pub enum DetectionResult {
     NotDetected,
 }
 
+/// Confidence level of a technique
+///
+/// This enum represents how reliable a detection of a technique is. Heuristics which may be
+/// fooled by the host, e.g. timing measurements on a loaded or throttled CPU, have a low
+/// confidence.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
 /// Error type for techniques
 ///
 /// This error type is used to represent errors that can occur when running a technique.
@@ -66,9 +94,27 @@ pub enum TechniqueError {
     Unknown,
 }
 
+/// Error type for the technique registry
+///
+/// This error type is used to represent errors that can occur when interacting with the registry,
+/// failures of a technique itself are represented by [`TechniqueError`].
+#[derive(Error, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DetectorError {
+    #[error("Technique {0} already registered")]
+    AlreadyRegistered(String),
+    #[error("Technique registry is already locked by the current thread")]
+    RegistryLocked,
+    #[error("Technique {0} not found")]
+    NotFound(String),
+    #[error("Invalid technique descriptor: {0}")]
+    InvalidDescriptor(String),
+    #[error("Technique descriptors can't be read: {0}")]
+    DescriptorDirectory(String),
+}
+
 /// A redpill technique
 /// This trait represents a redpill technique that can be used to detect the presence of the Xen hypervisor.
-/// It contains a name, a description, and an execute function.
+/// It contains a name, a description, a category, and an execute function.
 ///
 /// # Example
 ///
@@ -76,18 +122,209 @@ pub enum TechniqueError {
 pub trait Technique: Send + Sync {
     fn name(&self) -> &'static str;
     fn description(&self) -> &'static str;
+    /// The category of the technique, e.g. `signature` or `time`
+    fn category(&self) -> &'static str {
+        "uncategorized"
+    }
+    /// A hint on how to defeat the technique, e.g. which guest setting to change
+    fn remediation(&self) -> Option<&'static str> {
+        None
+    }
+    /// How reliable a detection of the technique is
+    fn confidence(&self) -> Confidence {
+        Confidence::default()
+    }
     fn execute(&self) -> TechniqueResult;
 }
 
+/// The outcome of a technique run
+///
+/// This struct contains the metadata of the technique alongside its result, so consumers
+/// don't have to query the registry again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TechniqueOutcome {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub category: &'static str,
+    pub remediation: Option<&'static str>,
+    pub confidence: Confidence,
+    pub result: TechniqueResult,
+}
+
+impl TechniqueOutcome {
+    /// Run a technique and collect its outcome
+    ///
+    /// # Arguments
+    ///
+    /// * `technique` - The technique to run
+    pub fn run(technique: &dyn Technique) -> Self {
+        debug!("Running technique: {}", technique.name());
+        TechniqueOutcome {
+            name: technique.name(),
+            description: technique.description(),
+            category: technique.category(),
+            remediation: technique.remediation(),
+            confidence: technique.confidence(),
+            result: technique.execute(),
+        }
+    }
+}
+
 impl Debug for dyn Technique {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Technique")
             .field("name", &self.name())
             .field("description", &self.description())
+            .field("category", &self.category())
+            .field("remediation", &self.remediation())
+            .field("confidence", &self.confidence())
             .finish()
     }
 }
 
+/// Descriptor of a [`DynamicTechnique`], as stored in a JSON file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TechniqueDescriptor {
+    name: String,
+    description: String,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    remediation: Option<String>,
+    #[serde(default)]
+    confidence: Confidence,
+    /// Program and arguments of the command implementing the technique
+    command: Vec<String>,
+}
+
+/// A technique loaded at runtime from a descriptor file
+///
+/// The descriptor is a JSON file giving the metadata of the technique and the command
+/// implementing it, e.g.:
+///
+/// ```json
+/// {
+///     "name": "Xen kernel module",
+///     "description": "Check if a Xen kernel module is loaded",
+///     "category": "artifact",
+///     "confidence": "high",
+///     "command": ["grep", "-q", "^xen", "/proc/modules"]
+/// }
+/// ```
+///
+/// The command exits with 0 when the hypervisor is detected and with 1 when it is not, any
+/// other outcome is a failure of the technique. The metadata of the technique are leaked to
+/// match the `'static` lifetime of the [`Technique`] trait, as techniques live as long as the
+/// registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicTechnique {
+    name: &'static str,
+    description: &'static str,
+    category: &'static str,
+    remediation: Option<&'static str>,
+    confidence: Confidence,
+    command: Vec<String>,
+}
+
+impl DynamicTechnique {
+    /// Load a technique from a descriptor file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the JSON descriptor
+    ///
+    /// # Returns
+    ///
+    /// The technique described by the file
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`DetectorError::InvalidDescriptor`] if the file can't be read or
+    /// parsed, if the name is empty or if the command has no program
+    pub fn from_descriptor(path: &Path) -> Result<Self, DetectorError> {
+        let invalid =
+            |e: String| DetectorError::InvalidDescriptor(format!("{}: {}", path.display(), e));
+
+        let descriptor = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let descriptor: TechniqueDescriptor =
+            serde_json::from_str(&descriptor).map_err(|e| invalid(e.to_string()))?;
+
+        if descriptor.name.trim().is_empty() {
+            return Err(invalid("empty technique name".to_string()));
+        }
+        if descriptor
+            .command
+            .first()
+            .is_none_or(|program| program.is_empty())
+        {
+            return Err(invalid("empty technique command".to_string()));
+        }
+
+        // Only valid descriptors are leaked
+        let leak = |value: String| &*Box::leak(value.into_boxed_str());
+        Ok(DynamicTechnique {
+            name: leak(descriptor.name),
+            description: leak(descriptor.description),
+            category: descriptor.category.map_or("dynamic", leak),
+            remediation: descriptor.remediation.map(leak),
+            confidence: descriptor.confidence,
+            command: descriptor.command,
+        })
+    }
+}
+
+impl Technique for DynamicTechnique {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    fn category(&self) -> &'static str {
+        self.category
+    }
+
+    fn remediation(&self) -> Option<&'static str> {
+        self.remediation
+    }
+
+    fn confidence(&self) -> Confidence {
+        self.confidence
+    }
+
+    fn execute(&self) -> TechniqueResult {
+        // The output of the command must not be mixed with the report printed on stdout
+        let output = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(|e| {
+                debug!("Command of technique {} failed: {}", self.name, e);
+                TechniqueError::Failed()
+            })?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.trim().is_empty() {
+            debug!(
+                "Command of technique {} wrote: {}",
+                self.name,
+                stderr.trim()
+            );
+        }
+
+        match output.status.code() {
+            Some(0) => Ok(DetectionResult::Detected),
+            Some(1) => Ok(DetectionResult::NotDetected),
+            _ => Err(TechniqueError::Failed()),
+        }
+    }
+}
+
 /// A registry of techniques
 #[derive(Debug, Default)]
 pub struct TechniqueRegistry {
@@ -118,12 +355,11 @@ impl TechniqueRegistry {
     /// # Errors
     ///
     /// This function returns an error if the technique is already registered
-    pub fn register<T: Technique + 'static>(&mut self, technique: T) -> Result<(), Box<dyn Error>> {
+    pub fn register<T: Technique + 'static>(&mut self, technique: T) -> Result<(), DetectorError> {
         if self.is_registered(&technique) {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::AlreadyExists,
-                "Technique already registered",
-            )));
+            return Err(DetectorError::AlreadyRegistered(
+                technique.name().to_string(),
+            ));
         }
 
         // Add the technique to the registry
@@ -132,6 +368,52 @@ impl TechniqueRegistry {
         Ok(())
     }
 
+    /// Load and register the techniques described in a directory
+    ///
+    /// Every `.json` file of the directory is loaded as a [`DynamicTechnique`], in file name
+    /// order. Malformed descriptors and techniques already registered are skipped with a
+    /// warning, so one broken descriptor doesn't prevent the others from loading.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - The directory containing the descriptors
+    ///
+    /// # Returns
+    ///
+    /// The number of registered techniques
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`DetectorError::DescriptorDirectory`] if the directory can't be read
+    pub fn load_directory(&mut self, directory: &Path) -> Result<usize, DetectorError> {
+        let directory_error = |e: std::io::Error| {
+            DetectorError::DescriptorDirectory(format!("{}: {}", directory.display(), e))
+        };
+
+        let mut descriptors = std::fs::read_dir(directory)
+            .map_err(directory_error)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()
+            .map_err(directory_error)?;
+        descriptors.retain(|path| path.is_file() && path.extension().is_some_and(|e| e == "json"));
+        descriptors.sort();
+
+        let mut registered = 0;
+        for descriptor in descriptors {
+            let result = DynamicTechnique::from_descriptor(&descriptor)
+                .and_then(|technique| self.register(technique));
+            match result {
+                Ok(()) => registered += 1,
+                Err(e) => warn!(
+                    "Skipping technique descriptor {}: {}",
+                    descriptor.display(),
+                    e
+                ),
+            }
+        }
+        Ok(registered)
+    }
+
     /// Check if a technique is already registered
     ///
     /// This function checks if a technique is already registered with the registry.
@@ -158,25 +440,135 @@ impl TechniqueRegistry {
         &self.techniques
     }
 
+    /// Run a single technique of the registry
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the technique to run
+    ///
+    /// # Returns
+    ///
+    /// The outcome of the technique
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if no technique with this name is registered
+    pub fn run_technique(&self, name: &str) -> Result<TechniqueOutcome, DetectorError> {
+        self.techniques
+            .iter()
+            .find(|technique| technique.name() == name)
+            .map(|technique| TechniqueOutcome::run(technique.as_ref()))
+            .ok_or_else(|| DetectorError::NotFound(name.to_string()))
+    }
+
+    /// List all techniques in the registry
+    ///
+    /// This function returns the name and description of each technique without running it.
+    ///
+    /// # Returns
+    ///
+    /// A list of technique names and descriptions, in registration order
+    pub fn list(&self) -> Vec<(String, &'static str)> {
+        self.techniques
+            .iter()
+            .map(|technique| (technique.name().to_string(), technique.description()))
+            .collect()
+    }
+
     /// Run all techniques in the registry
     ///
-    /// This function runs all techniques in the registry and returns a list of results.
+    /// This function runs all techniques in the registry and returns a list of outcomes.
+    ///
+    /// # Returns
+    ///
+    /// A list of outcomes containing the metadata and the result of each technique
+    pub fn run_all_techniques(&self) -> Vec<TechniqueOutcome> {
+        self.techniques
+            .iter()
+            .map(|technique| TechniqueOutcome::run(technique.as_ref()))
+            .collect()
+    }
+
+    /// Check if any technique in the registry detects the hypervisor
+    ///
+    /// This function runs techniques from the highest to the lowest confidence, in registration
+    /// order for a same confidence, and stops as soon as one of them reports a detection, which
+    /// is much cheaper than running every technique when only a boolean is needed. Failing
+    /// techniques are logged and skipped.
     ///
     /// # Returns
     ///
-    /// A list of tuples containing the technique and the result of the technique
-    #[allow(clippy::borrowed_box)] // would have to refactor the whole file to fix this
-    pub fn run_all_techniques(&self) -> Vec<(&Box<dyn Technique>, TechniqueResult)> {
-        let mut results = Vec::new();
-        for technique in self.techniques.iter() {
+    /// A boolean indicating whether a technique detected the hypervisor
+    pub fn detect_any(&self) -> Result<bool, DetectorError> {
+        let mut techniques = self.techniques.iter().collect::<Vec<_>>();
+        techniques.sort_by_key(|technique| std::cmp::Reverse(technique.confidence()));
+
+        for technique in techniques {
             debug!("Running technique: {}", technique.name());
-            let result = technique.execute();
-            results.push((technique, result));
+            match technique.execute() {
+                Ok(DetectionResult::Detected) => return Ok(true),
+                Ok(DetectionResult::NotDetected) => {}
+                Err(e) => debug!("Technique {} failed: {}", technique.name(), e),
+            }
         }
-        results
+        Ok(false)
     }
 }
 
+/// Guard of the global registry, tracking that the current thread holds its lock
+struct RegistryGuard(MutexGuard<'static, TechniqueRegistry>);
+
+impl Deref for RegistryGuard {
+    type Target = TechniqueRegistry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for RegistryGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for RegistryGuard {
+    fn drop(&mut self) {
+        REGISTRY_HELD.set(false);
+    }
+}
+
+/// Lock the global registry
+///
+/// Other threads wait for the lock. The registry is locked while techniques run, so a
+/// technique using the registry would deadlock: re-entrant calls fail instead.
+///
+/// A technique panicking while the registry is locked poisons the mutex. The registry is only
+/// mutated by pushing a fully built technique, so it is always consistent and the poison is
+/// cleared instead of failing every subsequent call.
+///
+/// # Returns
+///
+/// A guard of the global registry
+///
+/// # Errors
+///
+/// This function returns [`DetectorError::RegistryLocked`] if the current thread already
+/// holds the registry
+fn registry() -> Result<RegistryGuard, DetectorError> {
+    if REGISTRY_HELD.get() {
+        return Err(DetectorError::RegistryLocked);
+    }
+
+    let registry = TECHNIQUE_REGISTRY.lock().unwrap_or_else(|e| {
+        warn!("Technique registry was poisoned by a panic, recovering");
+        TECHNIQUE_REGISTRY.clear_poison();
+        e.into_inner()
+    });
+    REGISTRY_HELD.set(true);
+    Ok(RegistryGuard(registry))
+}
+
 /// Wrapper function to safely register a technique with the global registry
 ///
 /// # Arguments
@@ -189,35 +581,102 @@ impl TechniqueRegistry {
 ///
 /// # Errors
 ///
-/// This function returns an error if the technique is already registered
-pub fn register_technique<T: Technique + 'static>(technique: T) -> Result<(), Box<dyn Error>> {
-    let mut registry = TECHNIQUE_REGISTRY.lock()?;
-    registry.register(technique)
+/// This function returns an error if the technique is already registered or if it is called
+/// from a technique run by the global registry
+pub fn register_technique<T: Technique + 'static>(technique: T) -> Result<(), DetectorError> {
+    registry()?.register(technique)
+}
+
+/// Load the techniques described in a directory into the global registry
+///
+/// See [`TechniqueRegistry::load_directory`] for more details.
+///
+/// # Arguments
+///
+/// * `directory` - The directory containing the descriptors
+///
+/// # Returns
+///
+/// The number of registered techniques
+///
+/// # Errors
+///
+/// This function returns an error if the directory can't be read or if it is called from a
+/// technique run by the global registry
+pub fn load_techniques(directory: &Path) -> Result<usize, DetectorError> {
+    registry()?.load_directory(directory)
 }
 
 /// Run all techniques in the global registry
 ///
-/// This function runs all techniques in the global registry and returns a list of results.
+/// This function runs all techniques in the global registry and returns a list of outcomes.
+///
+/// # Returns
+///
+/// A list of outcomes containing the metadata and the result of each technique
+///
+/// # Errors
+///
+/// This function returns an error if it is called from a technique run by the global registry
+pub fn run_all_techniques() -> Result<Vec<TechniqueOutcome>, DetectorError> {
+    Ok(registry()?.run_all_techniques())
+}
+
+/// Run a single technique of the global registry
+///
+/// See [`TechniqueRegistry::run_technique`] for more details.
+///
+/// # Arguments
+///
+/// * `name` - The name of the technique to run
 ///
 /// # Returns
 ///
-/// A list of tuples containing the name of the technique and the result of the technique
+/// The outcome of the technique
 ///
 /// # Errors
 ///
-/// This function returns an error if the global registry is locked
-pub fn run_all_techniques() -> Result<Vec<(String, TechniqueResult)>, Box<dyn Error>> {
-    let registry = TECHNIQUE_REGISTRY.lock()?;
-    let results = registry
-        .run_all_techniques()
-        .into_iter()
-        .map(|(technique, result)| (technique.name().to_string(), result))
-        .collect();
-    Ok(results)
+/// This function returns an error if the technique is not registered or if it is called
+/// from a technique run by the global registry
+pub fn run_technique(name: &str) -> Result<TechniqueOutcome, DetectorError> {
+    registry()?.run_technique(name)
+}
+
+/// List all techniques in the global registry
+///
+/// See [`TechniqueRegistry::list`] for more details.
+///
+/// # Returns
+///
+/// A list of technique names and descriptions
+///
+/// # Errors
+///
+/// This function returns an error if it is called from a technique run by the global registry
+pub fn list_techniques() -> Result<Vec<(String, &'static str)>, DetectorError> {
+    Ok(registry()?.list())
+}
+
+/// Check if any technique in the global registry detects the hypervisor
+///
+/// See [`TechniqueRegistry::detect_any`] for more details.
+///
+/// # Returns
+///
+/// A boolean indicating whether a technique detected the hypervisor
+///
+/// # Errors
+///
+/// This function returns an error if it is called from a technique run by the global registry
+pub fn detect_any() -> Result<bool, DetectorError> {
+    registry()?.detect_any()
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use super::*;
 
     #[derive(Clone, Copy)]
@@ -258,7 +717,7 @@ mod tests {
         assert!(matches!(technique_registry.register(technique), Ok(())));
         assert!(matches!(
             technique_registry.register(technique),
-            Err(e) if e.to_string() == "Technique already registered"
+            Err(DetectorError::AlreadyRegistered(name)) if name == "TestTechnique"
         ));
     }
 
@@ -269,9 +728,379 @@ mod tests {
 
         assert!(matches!(technique_registry.register(technique), Ok(())));
 
-        let results = technique_registry.run_all_techniques();
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].0.name(), "TestTechnique");
-        assert_eq!(results[0].1, Ok(DetectionResult::Detected));
+        let outcomes = technique_registry.run_all_techniques();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].name, "TestTechnique");
+        assert_eq!(outcomes[0].description, "Test technique");
+        assert_eq!(outcomes[0].category, "uncategorized");
+        assert_eq!(outcomes[0].remediation, None);
+        assert_eq!(outcomes[0].result, Ok(DetectionResult::Detected));
+    }
+
+    #[test]
+    fn test_list_techniques() {
+        let mut technique_registry = TechniqueRegistry::new();
+        assert!(technique_registry.list().is_empty());
+
+        for name in ["First", "Second"] {
+            assert!(matches!(
+                technique_registry.register(CountingTechnique {
+                    name,
+                    result: Ok(DetectionResult::NotDetected),
+                    confidence: Confidence::Medium,
+                    runs: Arc::new(AtomicUsize::new(0)),
+                }),
+                Ok(())
+            ));
+        }
+
+        assert_eq!(
+            technique_registry.list(),
+            vec![
+                ("First".to_string(), "Counting technique"),
+                ("Second".to_string(), "Counting technique"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_global_list_techniques() {
+        // The global registry is populated by the static initializers of the techniques
+        let techniques = list_techniques().unwrap();
+        assert!(techniques.iter().any(|(name, _)| name == "DMI strings"));
+    }
+
+    #[test]
+    fn test_poisoned_registry_recovery() {
+        // Panic while holding the global registry lock to poison it
+        let result = std::thread::spawn(|| {
+            let _registry = TECHNIQUE_REGISTRY.lock();
+            panic!("technique panicked");
+        })
+        .join();
+        assert!(result.is_err());
+
+        assert!(list_techniques().is_ok());
+        assert!(!TECHNIQUE_REGISTRY.is_poisoned());
+    }
+
+    #[test]
+    fn test_global_registry_reentrant() {
+        // A technique using the registry while it runs, which would otherwise deadlock
+        let registry = registry().unwrap();
+        assert!(matches!(
+            list_techniques(),
+            Err(DetectorError::RegistryLocked)
+        ));
+        drop(registry);
+
+        assert!(list_techniques().is_ok());
+    }
+
+    #[test]
+    fn test_global_registry_contention() {
+        // Another thread holding the registry is waited for
+        let (locked, wait) = std::sync::mpsc::channel();
+        let holder = std::thread::spawn(move || {
+            let _registry = registry().unwrap();
+            locked.send(()).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        });
+        wait.recv().unwrap();
+
+        assert!(list_techniques().is_ok());
+        holder.join().unwrap();
+    }
+
+    #[test]
+    fn test_global_register_technique() {
+        // Reuse the name of a technique registered by the static initializers, so that the
+        // global registry is left untouched
+        let technique = CountingTechnique {
+            name: "DMI strings",
+            result: Ok(DetectionResult::NotDetected),
+            confidence: Confidence::Medium,
+            runs: Arc::new(AtomicUsize::new(0)),
+        };
+
+        assert!(matches!(
+            register_technique(technique),
+            Err(DetectorError::AlreadyRegistered(name)) if name == "DMI strings"
+        ));
+    }
+
+    #[test]
+    fn test_run_technique() {
+        let mut technique_registry = TechniqueRegistry::new();
+        assert!(matches!(technique_registry.register(TestTechnique), Ok(())));
+
+        let outcome = technique_registry.run_technique("TestTechnique").unwrap();
+        assert_eq!(outcome.result, Ok(DetectionResult::Detected));
+        assert!(matches!(
+            technique_registry.run_technique("Unknown"),
+            Err(DetectorError::NotFound(name)) if name == "Unknown"
+        ));
+    }
+
+    struct RemediableTechnique;
+
+    impl Technique for RemediableTechnique {
+        fn name(&self) -> &'static str {
+            "RemediableTechnique"
+        }
+
+        fn description(&self) -> &'static str {
+            "Remediable technique"
+        }
+
+        fn remediation(&self) -> Option<&'static str> {
+            Some("Change the guest configuration")
+        }
+
+        fn execute(&self) -> TechniqueResult {
+            Ok(DetectionResult::Detected)
+        }
+    }
+
+    #[test]
+    fn test_technique_remediation() {
+        let mut technique_registry = TechniqueRegistry::new();
+        assert!(matches!(
+            technique_registry.register(RemediableTechnique),
+            Ok(())
+        ));
+
+        let outcome = technique_registry
+            .run_technique("RemediableTechnique")
+            .unwrap();
+        assert_eq!(outcome.remediation, Some("Change the guest configuration"));
+    }
+
+    #[test]
+    fn test_global_technique_remediation() {
+        // Set by the `remediation` argument of the `#[technique]` macro
+        let outcome = run_technique("CPU Brand").unwrap();
+        assert!(outcome.remediation.is_some());
+        let outcome = run_technique("Hardware threads count").unwrap();
+        assert_eq!(outcome.remediation, None);
+    }
+
+    struct CountingTechnique {
+        name: &'static str,
+        result: TechniqueResult,
+        confidence: Confidence,
+        runs: Arc<AtomicUsize>,
+    }
+
+    impl Technique for CountingTechnique {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn description(&self) -> &'static str {
+            "Counting technique"
+        }
+
+        fn confidence(&self) -> Confidence {
+            self.confidence
+        }
+
+        fn execute(&self) -> TechniqueResult {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            self.result.clone()
+        }
+    }
+
+    #[test]
+    fn test_detect_any() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let technique = |name, result| CountingTechnique {
+            name,
+            result,
+            confidence: Confidence::Medium,
+            runs: runs.clone(),
+        };
+
+        let mut technique_registry = TechniqueRegistry::new();
+        assert!(matches!(technique_registry.detect_any(), Ok(false)));
+
+        for (name, result) in [
+            ("NotDetected", Ok(DetectionResult::NotDetected)),
+            ("Failed", Err(TechniqueError::Failed())),
+            ("Detected", Ok(DetectionResult::Detected)),
+            ("Skipped", Ok(DetectionResult::NotDetected)),
+        ] {
+            assert!(matches!(
+                technique_registry.register(technique(name, result)),
+                Ok(())
+            ));
+        }
+
+        assert!(matches!(technique_registry.detect_any(), Ok(true)));
+        // The last technique is skipped after the first detection
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_detect_any_without_detection() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut technique_registry = TechniqueRegistry::new();
+        assert!(matches!(
+            technique_registry.register(CountingTechnique {
+                name: "NotDetected",
+                result: Ok(DetectionResult::NotDetected),
+                confidence: Confidence::Medium,
+                runs: runs.clone(),
+            }),
+            Ok(())
+        ));
+
+        assert!(matches!(technique_registry.detect_any(), Ok(false)));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_detect_any_by_confidence() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let technique = |name, confidence| CountingTechnique {
+            name,
+            result: Ok(DetectionResult::Detected),
+            confidence,
+            runs: runs.clone(),
+        };
+
+        let mut technique_registry = TechniqueRegistry::new();
+        for (name, confidence) in [("Low", Confidence::Low), ("High", Confidence::High)] {
+            assert!(matches!(
+                technique_registry.register(technique(name, confidence)),
+                Ok(())
+            ));
+        }
+
+        // The high confidence technique runs first and stops the detection
+        assert!(matches!(technique_registry.detect_any(), Ok(true)));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        let outcome = technique_registry.run_technique("Low").unwrap();
+        assert_eq!(outcome.confidence, Confidence::Low);
+    }
+
+    /// Write a technique descriptor in a directory
+    fn write_descriptor(directory: &Path, file_name: &str, descriptor: &str) {
+        std::fs::write(directory.join(file_name), descriptor).unwrap();
+    }
+
+    #[test]
+    fn test_load_directory() {
+        let directory = tempfile::tempdir().unwrap();
+        write_descriptor(
+            directory.path(),
+            "detected.json",
+            r#"{
+                "name": "Dynamic detected",
+                "description": "Always detects",
+                "category": "artifact",
+                "confidence": "high",
+                "remediation": "Nothing to do",
+                "command": ["true"]
+            }"#,
+        );
+        write_descriptor(
+            directory.path(),
+            "not-detected.json",
+            r#"{"name": "Dynamic not detected", "description": "Never detects", "command": ["false"]}"#,
+        );
+        // Malformed descriptors and other files are skipped
+        write_descriptor(directory.path(), "invalid.json", "{");
+        write_descriptor(
+            directory.path(),
+            "empty-command.json",
+            r#"{"name": "Empty command", "description": "No command", "command": []}"#,
+        );
+        write_descriptor(directory.path(), "README.md", "Not a descriptor");
+
+        let mut technique_registry = TechniqueRegistry::new();
+        assert!(matches!(
+            technique_registry.load_directory(directory.path()),
+            Ok(2)
+        ));
+        assert_eq!(
+            technique_registry.list(),
+            vec![
+                ("Dynamic detected".to_string(), "Always detects"),
+                ("Dynamic not detected".to_string(), "Never detects"),
+            ]
+        );
+
+        let outcome = technique_registry
+            .run_technique("Dynamic detected")
+            .unwrap();
+        assert_eq!(outcome.result, Ok(DetectionResult::Detected));
+        assert_eq!(outcome.category, "artifact");
+        assert_eq!(outcome.confidence, Confidence::High);
+        assert_eq!(outcome.remediation, Some("Nothing to do"));
+
+        let outcome = technique_registry
+            .run_technique("Dynamic not detected")
+            .unwrap();
+        assert_eq!(outcome.result, Ok(DetectionResult::NotDetected));
+        assert_eq!(outcome.category, "dynamic");
+        assert_eq!(outcome.confidence, Confidence::Medium);
+
+        // Techniques already registered are skipped
+        assert!(matches!(
+            technique_registry.load_directory(directory.path()),
+            Ok(0)
+        ));
+        assert!(matches!(
+            technique_registry.load_directory(&directory.path().join("missing")),
+            Err(DetectorError::DescriptorDirectory(_))
+        ));
+    }
+
+    #[test]
+    fn test_dynamic_technique_from_descriptor() {
+        let directory = tempfile::tempdir().unwrap();
+        write_descriptor(
+            directory.path(),
+            "unknown-field.json",
+            r#"{"name": "Unknown field", "description": "", "command": ["true"], "wasm": "module.wasm"}"#,
+        );
+        assert!(matches!(
+            DynamicTechnique::from_descriptor(&directory.path().join("unknown-field.json")),
+            Err(DetectorError::InvalidDescriptor(_))
+        ));
+
+        write_descriptor(
+            directory.path(),
+            "failing.json",
+            r#"{"name": "Failing", "description": "", "command": ["sh", "-c", "exit 2"]}"#,
+        );
+        let technique =
+            DynamicTechnique::from_descriptor(&directory.path().join("failing.json")).unwrap();
+        assert_eq!(technique.execute(), Err(TechniqueError::Failed()));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_dynamic_technique_output() {
+        // Detects only if its output doesn't reach the stdout of the detector
+        let directory = tempfile::tempdir().unwrap();
+        write_descriptor(
+            directory.path(),
+            "verbose.json",
+            r#"{
+                "name": "Verbose",
+                "description": "Prints to stdout and stderr",
+                "command": [
+                    "sh",
+                    "-c",
+                    "echo plugin output; echo plugin error >&2; test \"$(readlink /proc/$$/fd/1)\" = /dev/null"
+                ]
+            }"#,
+        );
+        let technique =
+            DynamicTechnique::from_descriptor(&directory.path().join("verbose.json")).unwrap();
+        assert_eq!(technique.execute(), Ok(DetectionResult::Detected));
     }
 }
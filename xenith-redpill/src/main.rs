@@ -15,21 +15,76 @@ GNU General Public License for more details.
 You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
+use std::path::PathBuf;
+
+use clap::Parser;
 use log::{LevelFilter, info, warn};
 
 use xenith_redpill::prelude::*;
 
+/// Detect the presence of the Xen hypervisor
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// List the available techniques without running them
+    #[arg(long)]
+    list: bool,
+    /// Print the detection report as JSON, without the log output
+    #[arg(long)]
+    json: bool,
+    /// Directory of technique descriptors to load in addition to the built-in techniques
+    #[arg(long)]
+    techniques_dir: Option<PathBuf>,
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     const LOG_LEVEL: LevelFilter = LevelFilter::Debug;
 
+    let args = Args::parse();
+
     let mut clog = colog::default_builder();
-    clog.filter(None, LOG_LEVEL);
+    clog.filter(
+        None,
+        if args.json {
+            LevelFilter::Error
+        } else {
+            LOG_LEVEL
+        },
+    );
     clog.init();
 
+    if let Some(techniques_dir) = &args.techniques_dir {
+        let loaded = load_techniques(techniques_dir)?;
+        info!(
+            "Loaded {} techniques from {}",
+            loaded,
+            techniques_dir.display()
+        );
+    }
+
+    if args.list {
+        for (name, description) in list_techniques()? {
+            println!("{}: {}", name, description);
+        }
+        return Ok(());
+    }
+
     info!("Running all detection techniques");
-    let results = run_all_techniques()?;
+    let outcomes = run_all_techniques()?;
+
+    if args.json {
+        println!("{}", DetectionReport::new(outcomes).to_json()?);
+        return Ok(());
+    }
 
-    for (name, result) in results {
+    for TechniqueOutcome {
+        name,
+        category,
+        result,
+        ..
+    } in outcomes
+    {
+        let name = format!("{} ({})", name, category);
         match result {
             Ok(DetectionResult::Detected) => {
                 warn!(
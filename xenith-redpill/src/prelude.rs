@@ -2,5 +2,10 @@
 //!
 //! This module contains re-exports of commonly used types and functions that are used throughout the crate.
 
-pub use crate::detector::run_all_techniques;
-pub use crate::detector::{DetectionResult, TechniqueError};
+pub use crate::detector::{
+    Confidence, DetectionResult, DetectorError, DynamicTechnique, TechniqueError, TechniqueOutcome,
+};
+pub use crate::detector::{
+    detect_any, list_techniques, load_techniques, run_all_techniques, run_technique,
+};
+pub use crate::report::DetectionReport;
@@ -0,0 +1,165 @@
+/*
+Xenith - Xen-based security hypervisor
+Copyright (C) 2025 Xenith contributors
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Detection report
+//!
+//! This module provides the [`DetectionReport`], which gathers the outcomes of a run of the
+//! techniques in a structured form, to be consumed by scripts as JSON.
+
+use serde::Serialize;
+
+use crate::detector::{Confidence, DetectionResult, TechniqueOutcome};
+
+/// The outcome of a technique, as written in a report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TechniqueReport {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub category: &'static str,
+    /// A hint on how to defeat the technique
+    pub remediation: Option<&'static str>,
+    /// How reliable a detection of the technique is
+    pub confidence: Confidence,
+    /// Whether the technique detected the hypervisor, `None` if it failed
+    pub detected: Option<bool>,
+    /// The error of the technique, if it failed
+    pub error: Option<String>,
+}
+
+impl From<TechniqueOutcome> for TechniqueReport {
+    fn from(outcome: TechniqueOutcome) -> Self {
+        let (detected, error) = match outcome.result {
+            Ok(result) => (Some(result == DetectionResult::Detected), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        TechniqueReport {
+            name: outcome.name,
+            description: outcome.description,
+            category: outcome.category,
+            remediation: outcome.remediation,
+            confidence: outcome.confidence,
+            detected,
+            error,
+        }
+    }
+}
+
+/// The report of a run of the techniques
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DetectionReport {
+    /// Whether any technique detected the hypervisor
+    pub detected: bool,
+    /// The outcome of each technique, in run order
+    pub techniques: Vec<TechniqueReport>,
+}
+
+impl DetectionReport {
+    /// Create a report from the outcomes of the techniques
+    ///
+    /// # Arguments
+    ///
+    /// * `outcomes` - The outcomes of the techniques
+    pub fn new(outcomes: Vec<TechniqueOutcome>) -> Self {
+        let techniques = outcomes
+            .into_iter()
+            .map(TechniqueReport::from)
+            .collect::<Vec<TechniqueReport>>();
+        let detected = techniques
+            .iter()
+            .any(|technique| technique.detected == Some(true));
+
+        DetectionReport {
+            detected,
+            techniques,
+        }
+    }
+
+    /// Serialize the report as JSON
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the report can't be serialized
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detector::TechniqueError;
+
+    #[test]
+    fn test_detection_report_json() {
+        let outcome = |name, result| TechniqueOutcome {
+            name,
+            description: "Test technique",
+            category: "test",
+            remediation: None,
+            confidence: Confidence::Low,
+            result,
+        };
+        let report = DetectionReport::new(vec![
+            outcome("Detected", Ok(DetectionResult::Detected)),
+            outcome("NotDetected", Ok(DetectionResult::NotDetected)),
+            outcome("Failed", Err(TechniqueError::NotImplemented)),
+        ]);
+        assert!(report.detected);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["detected"], true);
+
+        let techniques = json["techniques"].as_array().unwrap();
+        assert_eq!(techniques.len(), 3);
+        assert_eq!(techniques[0]["name"], "Detected");
+        assert_eq!(techniques[0]["description"], "Test technique");
+        assert_eq!(techniques[0]["category"], "test");
+        assert_eq!(techniques[0]["detected"], true);
+        assert!(techniques[0]["error"].is_null());
+        assert_eq!(techniques[1]["detected"], false);
+        assert!(techniques[2]["detected"].is_null());
+        assert_eq!(techniques[2]["error"], "Technique not implemented");
+        assert!(techniques[0]["remediation"].is_null());
+        assert_eq!(techniques[0]["confidence"], "low");
+
+        let report = DetectionReport::new(vec![TechniqueOutcome {
+            remediation: Some("Spoof the CPUID brand string"),
+            ..outcome("Remediable", Ok(DetectionResult::Detected))
+        }]);
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json["techniques"][0]["remediation"],
+            "Spoof the CPUID brand string"
+        );
+    }
+
+    #[test]
+    fn test_detection_report_not_detected() {
+        let report = DetectionReport::new(vec![TechniqueOutcome {
+            name: "Failed",
+            description: "Test technique",
+            category: "test",
+            remediation: None,
+            confidence: Confidence::Low,
+            result: Err(TechniqueError::Failed()),
+        }]);
+        assert!(!report.detected);
+        assert!(DetectionReport::new(Vec::new()).techniques.is_empty());
+    }
+}
@@ -21,6 +21,7 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 //! This module contains the various techniques used to detect the presence of the Xen hypervisor
 //! by analyzing different aspects of the system.
 
+pub mod artifact;
 pub mod behavior;
 pub mod signature;
 pub mod time;
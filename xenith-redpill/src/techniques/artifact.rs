@@ -0,0 +1,616 @@
+/*
+Xenith - Xen-based security hypervisor
+Copyright (C) 2025 Xenith contributors
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! # Artifact-Based Detection
+//!
+//! This module implements artifact-based techniques to detect the presence of the Xen hypervisor
+//! by looking for devices, drivers and files left by the hypervisor in the guest.
+
+use log::error;
+use static_init::dynamic;
+
+use crate::{
+    detector::{Confidence, DetectionResult, Technique, TechniqueResult, register_technique},
+    prelude::TechniqueError,
+};
+
+use xenith_redpill_macros::technique;
+
+/// PCI vendor ID of the Xen platform device (XenSource, Inc.)
+pub const XEN_PLATFORM_PCI_VENDOR_ID: u16 = 0x5853;
+/// PCI device IDs of the Xen platform device
+pub const XEN_PLATFORM_PCI_DEVICE_IDS: [u16; 2] = [0x0001, 0x0002];
+
+/// Check if the Xen platform PCI device is in a list of PCI devices
+///
+/// # Arguments
+///
+/// * `devices` - A list of (vendor ID, device ID) pairs
+///
+/// # Returns
+///
+/// A boolean indicating whether the Xen platform PCI device is in the list
+fn has_xen_platform_device(devices: &[(u16, u16)]) -> bool {
+    devices.iter().any(|(vendor, device)| {
+        *vendor == XEN_PLATFORM_PCI_VENDOR_ID && XEN_PLATFORM_PCI_DEVICE_IDS.contains(device)
+    })
+}
+
+/// Enumerate the PCI devices of the system
+///
+/// # Returns
+///
+/// A list of (vendor ID, device ID) pairs
+#[cfg(target_os = "linux")]
+fn pci_devices() -> Result<Vec<(u16, u16)>, TechniqueError> {
+    use std::fs;
+
+    let read_id = |path: std::path::PathBuf| -> Result<u16, TechniqueError> {
+        let id = fs::read_to_string(path).map_err(|_| TechniqueError::Failed())?;
+        u16::from_str_radix(id.trim().trim_start_matches("0x"), 16)
+            .map_err(|_| TechniqueError::Failed())
+    };
+
+    let entries = fs::read_dir("/sys/bus/pci/devices").map_err(|_| TechniqueError::Failed())?;
+
+    let mut devices = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|_| TechniqueError::Failed())?.path();
+        devices.push((read_id(path.join("vendor"))?, read_id(path.join("device"))?));
+    }
+
+    Ok(devices)
+}
+
+/// Enumerate the PCI devices of the system
+#[cfg(not(target_os = "linux"))]
+fn pci_devices() -> Result<Vec<(u16, u16)>, TechniqueError> {
+    Err(TechniqueError::NotImplemented)
+}
+
+#[technique(
+    name = "Xen platform PCI device",
+    description = "Check for the Xen platform PCI device (vendor 0x5853, device 0x0001 or 0x0002) exposed to HVM guests",
+    os = "all"
+)]
+fn xen_platform_pci_device() -> TechniqueResult {
+    let devices = pci_devices()?;
+
+    if has_xen_platform_device(&devices) {
+        return Ok(DetectionResult::Detected);
+    }
+
+    Ok(DetectionResult::NotDetected)
+}
+
+/// DMI fields exposing the SMBIOS strings of the system, as named in `/sys/class/dmi/id`
+#[cfg(target_os = "linux")]
+const DMI_FIELDS: [&str; 8] = [
+    "bios_vendor",
+    "bios_version",
+    "sys_vendor",
+    "product_name",
+    "product_version",
+    "board_vendor",
+    "board_name",
+    "chassis_vendor",
+];
+
+/// Check if any DMI string contains a reference to Xen
+///
+/// # Arguments
+///
+/// * `values` - The DMI strings to check
+///
+/// # Returns
+///
+/// A boolean indicating whether any DMI string contains "xen" (case-insensitive)
+fn dmi_contains_xen(values: &[String]) -> bool {
+    values
+        .iter()
+        .any(|value| value.to_lowercase().contains("xen"))
+}
+
+/// Read the DMI strings of the system
+///
+/// Fields which don't exist or can't be read (e.g. serial numbers are only readable by
+/// root) are skipped.
+///
+/// # Returns
+///
+/// The list of DMI strings which could be read
+#[cfg(target_os = "linux")]
+fn dmi_strings() -> Result<Vec<String>, TechniqueError> {
+    let dmi_path = std::path::Path::new("/sys/class/dmi/id");
+    if !dmi_path.is_dir() {
+        return Err(TechniqueError::Failed());
+    }
+
+    let values = DMI_FIELDS
+        .iter()
+        .filter_map(|field| std::fs::read_to_string(dmi_path.join(field)).ok())
+        .map(|value| value.trim().to_string())
+        .collect();
+
+    Ok(values)
+}
+
+/// Read the DMI strings of the system
+#[cfg(not(target_os = "linux"))]
+fn dmi_strings() -> Result<Vec<String>, TechniqueError> {
+    Err(TechniqueError::NotImplemented)
+}
+
+#[technique(
+    name = "DMI strings",
+    description = "Check if any SMBIOS string (system manufacturer, product name, BIOS vendor, ...) contains \"Xen\"",
+    os = "all",
+    remediation = "Set realistic SMBIOS strings for every field, e.g. with the xenith-vm SmBios"
+)]
+fn dmi_strings_scan() -> TechniqueResult {
+    let values = dmi_strings()?;
+
+    if dmi_contains_xen(&values) {
+        return Ok(DetectionResult::Detected);
+    }
+
+    Ok(DetectionResult::NotDetected)
+}
+
+/// Path of the MSR device of the first CPU, provided by the `msr` kernel module
+#[cfg(target_os = "linux")]
+const MSR_DEVICE_PATH: &str = "/dev/cpu/0/msr";
+/// First MSR of the range reserved for hypervisors, used by Xen for the hypercall page
+#[cfg(target_os = "linux")]
+const XEN_HYPERCALL_PAGE_MSR: u64 = 0x4000_0000;
+
+/// Read a MSR through the Linux `msr` device
+///
+/// The kernel executes `rdmsr` with an exception handler and turns a general protection
+/// fault into an `EIO` error, so reading a MSR that doesn't exist can't crash the process.
+///
+/// # Arguments
+///
+/// * `device` - Path of the MSR device, e.g. `/dev/cpu/0/msr`
+/// * `msr` - Index of the MSR to read
+///
+/// # Returns
+///
+/// The value of the MSR, or `None` if reading it faulted
+///
+/// # Errors
+///
+/// Returns [`TechniqueError::Failed`] if the MSR device can't be opened, which is the case
+/// when the `msr` module isn't loaded or the process lacks the `CAP_SYS_RAWIO` capability
+#[cfg(target_os = "linux")]
+fn read_msr(device: &std::path::Path, msr: u64) -> Result<Option<u64>, TechniqueError> {
+    use std::fs::File;
+    use std::os::unix::fs::FileExt;
+
+    let file = File::open(device).map_err(|_| TechniqueError::Failed())?;
+
+    let mut value = [0u8; 8];
+    match file.read_exact_at(&mut value, msr) {
+        Ok(()) => Ok(Some(u64::from_le_bytes(value))),
+        Err(_) => Ok(None),
+    }
+}
+
+#[technique(
+    name = "Xen hypervisor MSR",
+    description = "Check if the first MSR of the hypervisor range (0x40000000) is readable, which faults on physical CPUs.
+    ⚠️ Requires root privileges (CAP_SYS_RAWIO) and the msr kernel module.",
+    os = "linux"
+)]
+fn xen_hypervisor_msr() -> TechniqueResult {
+    let value = read_msr(
+        std::path::Path::new(MSR_DEVICE_PATH),
+        XEN_HYPERCALL_PAGE_MSR,
+    )?;
+
+    if value.is_some() {
+        return Ok(DetectionResult::Detected);
+    }
+
+    Ok(DetectionResult::NotDetected)
+}
+
+/// Path of the hypervisor sysfs directory, populated by the Xen drivers of Linux guests
+#[cfg(target_os = "linux")]
+const SYS_HYPERVISOR_PATH: &str = "/sys/hypervisor";
+
+/// Read a file of the hypervisor sysfs directory
+///
+/// # Returns
+///
+/// The trimmed content of the file, or `None` if it doesn't exist
+#[cfg(target_os = "linux")]
+fn read_sys_hypervisor_file(path: &std::path::Path) -> Result<Option<String>, TechniqueError> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content.trim().to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(_) => Err(TechniqueError::Failed()),
+    }
+}
+
+/// Check if the hypervisor sysfs directory exposes Xen
+///
+/// Xen guests list the supported guest ABIs in `properties/capabilities` (e.g.
+/// `xen-3.0-x86_64 hvm-3.0-x86_32`) and the domain UUID in `uuid`.
+///
+/// # Arguments
+///
+/// * `base` - Path of the hypervisor sysfs directory, usually `/sys/hypervisor`
+///
+/// # Returns
+///
+/// A boolean indicating whether a Xen capability or UUID is exposed, missing files are
+/// not an error as non-Xen systems don't have them
+#[cfg(target_os = "linux")]
+fn sys_hypervisor_exposes_xen(base: &std::path::Path) -> Result<bool, TechniqueError> {
+    let capabilities = read_sys_hypervisor_file(&base.join("properties/capabilities"))?;
+    let has_xen_capability = capabilities.is_some_and(|capabilities| {
+        capabilities
+            .split_whitespace()
+            .any(|abi| abi.starts_with("xen-") || abi.starts_with("hvm-"))
+    });
+
+    let uuid = read_sys_hypervisor_file(&base.join("uuid"))?;
+    let has_uuid = uuid.is_some_and(|uuid| {
+        uuid.len() == 36 && uuid.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+    });
+
+    Ok(has_xen_capability || has_uuid)
+}
+
+#[technique(
+    name = "Xen sysfs hypervisor properties",
+    description = "Check if /sys/hypervisor exposes Xen capabilities or a domain UUID, which are provided by the Xen drivers",
+    os = "linux",
+    confidence = "high"
+)]
+fn xen_sys_hypervisor() -> TechniqueResult {
+    if sys_hypervisor_exposes_xen(std::path::Path::new(SYS_HYPERVISOR_PATH))? {
+        return Ok(DetectionResult::Detected);
+    }
+
+    Ok(DetectionResult::NotDetected)
+}
+
+/// Path of the sysfs root
+#[cfg(target_os = "linux")]
+const SYS_PATH: &str = "/sys";
+/// Prefixes of the Xen PV block (`vbd-51712`) and network (`vif-0`) devices on the xen bus
+#[cfg(target_os = "linux")]
+const XEN_PV_DEVICE_PREFIXES: [&str; 2] = ["vbd-", "vif-"];
+/// Prefix of the block devices provided by xen-blkfront
+#[cfg(target_os = "linux")]
+const XEN_BLOCK_DEVICE_PREFIX: &str = "xvd";
+
+/// List the entries of a sysfs directory
+///
+/// # Returns
+///
+/// The names of the entries, or an empty list if the directory doesn't exist
+#[cfg(target_os = "linux")]
+fn sysfs_entries(path: &std::path::Path) -> Result<Vec<String>, TechniqueError> {
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(_) => return Err(TechniqueError::Failed()),
+    };
+
+    entries
+        .map(|entry| {
+            entry
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .map_err(|_| TechniqueError::Failed())
+        })
+        .collect()
+}
+
+/// Check if Xen PV block or network devices are present
+///
+/// PV and PVHVM Linux guests register the devices of xen-blkfront and xen-netfront on the
+/// xen bus, and the disks are named `xvd*`.
+///
+/// # Arguments
+///
+/// * `sysfs` - Path of the sysfs root, usually `/sys`
+///
+/// # Returns
+///
+/// A boolean indicating whether a Xen PV device is present, missing directories are not an
+/// error as non-Xen systems don't have a xen bus
+#[cfg(target_os = "linux")]
+fn xen_pv_devices_present(sysfs: &std::path::Path) -> Result<bool, TechniqueError> {
+    let has_bus_device = sysfs_entries(&sysfs.join("bus/xen/devices"))?
+        .iter()
+        .any(|device| {
+            XEN_PV_DEVICE_PREFIXES
+                .iter()
+                .any(|prefix| device.starts_with(prefix))
+        });
+    let has_block_device = sysfs_entries(&sysfs.join("block"))?
+        .iter()
+        .any(|device| device.starts_with(XEN_BLOCK_DEVICE_PREFIX));
+
+    Ok(has_bus_device || has_block_device)
+}
+
+#[technique(
+    name = "Xen PV devices",
+    description = "Check for Xen paravirtual block or network devices (xen bus vbd/vif devices, xvd* disks), which are used by PV and PVHVM guests",
+    os = "linux",
+    confidence = "high"
+)]
+fn xen_pv_devices() -> TechniqueResult {
+    if xen_pv_devices_present(std::path::Path::new(SYS_PATH))? {
+        return Ok(DetectionResult::Detected);
+    }
+
+    Ok(DetectionResult::NotDetected)
+}
+
+/// Path of the procfs root
+#[cfg(target_os = "linux")]
+const PROC_PATH: &str = "/proc";
+
+/// Read a file of procfs
+///
+/// # Returns
+///
+/// The content of the file, or `None` if it doesn't exist
+///
+/// # Errors
+///
+/// Returns [`TechniqueError::Failed`] if the file can't be read, e.g. without permission
+#[cfg(target_os = "linux")]
+fn read_proc_file(path: &std::path::Path) -> Result<Option<String>, TechniqueError> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(_) => Err(TechniqueError::Failed()),
+    }
+}
+
+/// Check if the physical memory map has a region reserved for Xen
+///
+/// HVM guests list the MMIO region of the Xen platform PCI device, claimed by the
+/// `xen-platform-pci` driver, and PV guests list regions such as the Xen scratch space.
+///
+/// # Arguments
+///
+/// * `iomem` - The content of `/proc/iomem`, whose addresses are zeroed for non-root users
+fn iomem_has_xen_region(iomem: &str) -> bool {
+    iomem
+        .lines()
+        .filter_map(|line| line.split_once(" : "))
+        .any(|(_, name)| name.to_lowercase().contains("xen"))
+}
+
+/// Check if the physical memory map has the PCI Express configuration space
+///
+/// PCI Express hosts reserve the memory-mapped configuration space (ECAM), listed as
+/// `PCI MMCONFIG`. Xen HVM guests emulate the legacy i440FX chipset without it.
+///
+/// # Arguments
+///
+/// * `iomem` - The content of `/proc/iomem`
+fn iomem_has_mmconfig(iomem: &str) -> bool {
+    iomem.contains("MMCONFIG")
+}
+
+/// Check if the memory map and MTRRs of the system show Xen peculiarities
+///
+/// A Xen region in `/proc/iomem` is a detection on its own. Otherwise, the system is
+/// flagged when both the PCI Express configuration space is missing and the MTRRs are not
+/// exposed, as Xen PV guests run without MTRRs. Either alone is not reliable: old physical
+/// machines may lack PCI Express and some kernels are built without MTRR support.
+///
+/// # Arguments
+///
+/// * `proc` - Path of the procfs root, usually `/proc`
+///
+/// # Errors
+///
+/// Returns [`TechniqueError::Failed`] if `/proc/iomem` is missing or if a file can't be read
+#[cfg(target_os = "linux")]
+fn memory_map_shows_xen(proc: &std::path::Path) -> Result<bool, TechniqueError> {
+    let iomem = read_proc_file(&proc.join("iomem"))?.ok_or(TechniqueError::Failed())?;
+    if iomem_has_xen_region(&iomem) {
+        return Ok(true);
+    }
+
+    let mtrr = read_proc_file(&proc.join("mtrr"))?;
+    Ok(!iomem_has_mmconfig(&iomem) && mtrr.is_none())
+}
+
+#[technique(
+    name = "Memory map and MTRR",
+    description = "Check /proc/iomem for Xen reserved regions, or for a missing PCI Express configuration space along with missing MTRRs.
+    ⚠️ Chipsets and kernel configurations vary, the absence of regions is only a hint.",
+    os = "linux"
+)]
+fn memory_map_mtrr() -> TechniqueResult {
+    if memory_map_shows_xen(std::path::Path::new(PROC_PATH))? {
+        return Ok(DetectionResult::Detected);
+    }
+
+    Ok(DetectionResult::NotDetected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_xen_platform_device() {
+        // Intel host bridge and Cirrus VGA, as seen in a Xen HVM guest
+        let mut devices = vec![(0x8086, 0x1237), (0x1013, 0x00b8)];
+        assert!(!has_xen_platform_device(&devices));
+
+        devices.push((0x5853, 0x0001));
+        assert!(has_xen_platform_device(&devices));
+
+        assert!(has_xen_platform_device(&[(0x5853, 0x0002)]));
+        assert!(!has_xen_platform_device(&[(0x5853, 0xc000)]));
+    }
+
+    #[test]
+    fn test_dmi_contains_xen() {
+        let mut values = vec![
+            "Dell Inc.".to_string(),
+            "PowerEdge R740".to_string(),
+            "2.12.2".to_string(),
+        ];
+        assert!(!dmi_contains_xen(&values));
+
+        values.push("HVM domU".to_string());
+        assert!(!dmi_contains_xen(&values));
+
+        values.push("Xen".to_string());
+        assert!(dmi_contains_xen(&values));
+
+        assert!(dmi_contains_xen(&["4.17.3-XEN".to_string()]));
+        assert!(!dmi_contains_xen(&[]));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_read_msr_without_device() {
+        let device = std::path::Path::new("/nonexistent/cpu/0/msr");
+        assert_eq!(
+            read_msr(device, XEN_HYPERCALL_PAGE_MSR),
+            Err(TechniqueError::Failed())
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_sys_hypervisor_exposes_xen() {
+        use std::fs;
+
+        let base = tempfile::tempdir().unwrap();
+        // Missing files are not an error
+        assert_eq!(sys_hypervisor_exposes_xen(base.path()), Ok(false));
+
+        fs::create_dir(base.path().join("properties")).unwrap();
+        fs::write(base.path().join("properties/capabilities"), "\n").unwrap();
+        assert_eq!(sys_hypervisor_exposes_xen(base.path()), Ok(false));
+
+        fs::write(
+            base.path().join("properties/capabilities"),
+            "xen-3.0-x86_64 xen-3.0-x86_32p hvm-3.0-x86_32 hvm-3.0-x86_32p hvm-3.0-x86_64\n",
+        )
+        .unwrap();
+        assert_eq!(sys_hypervisor_exposes_xen(base.path()), Ok(true));
+
+        fs::remove_file(base.path().join("properties/capabilities")).unwrap();
+        fs::write(
+            base.path().join("uuid"),
+            "6c1a6d3e-9a2b-4c5d-8e7f-0a1b2c3d4e5f\n",
+        )
+        .unwrap();
+        assert_eq!(sys_hypervisor_exposes_xen(base.path()), Ok(true));
+
+        fs::write(base.path().join("uuid"), "not a uuid\n").unwrap();
+        assert_eq!(sys_hypervisor_exposes_xen(base.path()), Ok(false));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_xen_pv_devices_present() {
+        use std::fs;
+
+        let sysfs = tempfile::tempdir().unwrap();
+        // Missing directories are not an error
+        assert_eq!(xen_pv_devices_present(sysfs.path()), Ok(false));
+
+        fs::create_dir_all(sysfs.path().join("block/sda")).unwrap();
+        fs::create_dir_all(sysfs.path().join("bus/xen/devices/console-0")).unwrap();
+        assert_eq!(xen_pv_devices_present(sysfs.path()), Ok(false));
+
+        fs::create_dir_all(sysfs.path().join("bus/xen/devices/vif-0")).unwrap();
+        assert_eq!(xen_pv_devices_present(sysfs.path()), Ok(true));
+
+        fs::remove_dir_all(sysfs.path().join("bus")).unwrap();
+        fs::create_dir_all(sysfs.path().join("block/xvda")).unwrap();
+        assert_eq!(xen_pv_devices_present(sysfs.path()), Ok(true));
+    }
+
+    /// Excerpt of the memory map of a Xen HVM guest, as seen by a non-root user
+    const XEN_HVM_IOMEM: &str = "\
+00000000-00000000 : Reserved
+00000000-00000000 : System RAM
+00000000-00000000 : PCI Bus 0000:00
+  00000000-00000000 : 0000:00:03.0
+    00000000-00000000 : xen-platform-pci
+00000000-00000000 : Reserved
+";
+
+    /// Excerpt of the memory map of a physical machine
+    const PHYSICAL_IOMEM: &str = "\
+00000000-00000000 : Reserved
+00000000-00000000 : System RAM
+00000000-00000000 : PCI MMCONFIG 0000 [bus 00-ff]
+  00000000-00000000 : Reserved
+00000000-00000000 : Local APIC
+";
+
+    #[test]
+    fn test_iomem_regions() {
+        assert!(iomem_has_xen_region(XEN_HVM_IOMEM));
+        assert!(!iomem_has_mmconfig(XEN_HVM_IOMEM));
+        assert!(!iomem_has_xen_region(PHYSICAL_IOMEM));
+        assert!(iomem_has_mmconfig(PHYSICAL_IOMEM));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_memory_map_shows_xen() {
+        use std::fs;
+
+        let proc = tempfile::tempdir().unwrap();
+        assert_eq!(
+            memory_map_shows_xen(proc.path()),
+            Err(TechniqueError::Failed())
+        );
+
+        fs::write(proc.path().join("iomem"), XEN_HVM_IOMEM).unwrap();
+        assert_eq!(memory_map_shows_xen(proc.path()), Ok(true));
+
+        fs::write(proc.path().join("iomem"), PHYSICAL_IOMEM).unwrap();
+        assert_eq!(memory_map_shows_xen(proc.path()), Ok(false));
+
+        // No PCI Express configuration space and no MTRRs
+        fs::write(
+            proc.path().join("iomem"),
+            "00000000-00000000 : System RAM\n",
+        )
+        .unwrap();
+        assert_eq!(memory_map_shows_xen(proc.path()), Ok(true));
+
+        fs::write(
+            proc.path().join("mtrr"),
+            "reg00: base=0x0f0000000 (3840MB), size=  256MB, count=1: uncachable\n",
+        )
+        .unwrap();
+        assert_eq!(memory_map_shows_xen(proc.path()), Ok(false));
+    }
+}
@@ -22,11 +22,11 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 //! by analyzing memory for known patterns or OS-specific structures.
 
 use log::error;
-use raw_cpuid::{CpuId, Hypervisor};
+use raw_cpuid::{CpuId, Hypervisor, cpuid};
 use static_init::dynamic;
 
 use crate::{
-    detector::{DetectionResult, Technique, TechniqueResult, register_technique},
+    detector::{Confidence, DetectionResult, Technique, TechniqueResult, register_technique},
     prelude::TechniqueError,
 };
 
@@ -56,7 +56,8 @@ fn vmid() -> TechniqueResult {
 #[technique(
     name = "CPU Brand",
     description = "Check if CPU brand model contains any VM-specific string snippets",
-    os = "all"
+    os = "all",
+    remediation = "Spoof the CPUID brand string (leaves 0x80000002 to 0x80000004), e.g. with the xenith-vm CpuModel brand"
 )]
 fn cpu_brand() -> TechniqueResult {
     let vm_brand = "xen";
@@ -132,3 +133,140 @@ fn hardware_threads_count() -> TechniqueResult {
 
     Ok(DetectionResult::NotDetected)
 }
+
+/// CPUID leaf reporting the highest hypervisor leaf in EAX
+const HYPERVISOR_BASE_LEAF: u32 = 0x4000_0000;
+/// Offset of the Xen timing leaf from the Xen base leaf. Its sub-leaf 0 reports the guest
+/// TSC frequency in kHz in ECX.
+const XEN_TIMING_LEAF_OFFSET: u32 = 3;
+/// Plausible TSC frequency range in kHz (100 MHz to 10 GHz)
+const TSC_FREQUENCY_RANGE_KHZ: std::ops::RangeInclusive<u32> = 100_000..=10_000_000;
+
+/// Check if the Xen timing leaf reports a TSC frequency
+///
+/// Xen places its leaves at 0x40000000, or at 0x40000100 when it exposes another hypervisor
+/// interface first. The maximum leaf of that range must cover the timing leaf before trusting
+/// it, as older Xen versions don't expose it.
+///
+/// # Arguments
+///
+/// * `base_leaf` - The leaf where the Xen signature was found
+/// * `max_leaf` - The highest Xen leaf, as reported by EAX of the base leaf
+/// * `tsc_frequency` - The guest TSC frequency in kHz, as reported by the timing leaf
+///
+/// # Returns
+///
+/// A boolean indicating whether a plausible TSC frequency is reported
+fn reports_tsc_frequency(base_leaf: u32, max_leaf: u32, tsc_frequency: u32) -> bool {
+    (base_leaf + XEN_TIMING_LEAF_OFFSET..base_leaf + 0x100).contains(&max_leaf)
+        && TSC_FREQUENCY_RANGE_KHZ.contains(&tsc_frequency)
+}
+
+#[technique(
+    name = "Hypervisor TSC frequency",
+    description = "Check if the Xen timing CPUID leaf (0x40000003, or 0x40000103 behind another hypervisor interface) reports the guest TSC frequency.
+    Physical CPUs don't implement the hypervisor leaves, and Xen versions without the timing leaf lead to false negatives.",
+    os = "all"
+)]
+fn hypervisor_tsc_frequency() -> TechniqueResult {
+    let Some(base_leaf) = [HYPERVISOR_BASE_LEAF, HYPERVISOR_SECONDARY_LEAF]
+        .into_iter()
+        .find(|&leaf| {
+            let leaf = cpuid!(leaf);
+            is_xen_signature(leaf.ebx, leaf.ecx, leaf.edx)
+        })
+    else {
+        return Ok(DetectionResult::NotDetected);
+    };
+
+    let max_leaf = cpuid!(base_leaf).eax;
+    let tsc_frequency = cpuid!(base_leaf + XEN_TIMING_LEAF_OFFSET, 0).ecx;
+    if reports_tsc_frequency(base_leaf, max_leaf, tsc_frequency) {
+        return Ok(DetectionResult::Detected);
+    }
+
+    Ok(DetectionResult::NotDetected)
+}
+
+/// CPUID leaf where Xen moves its leaves when it exposes another hypervisor interface
+/// (e.g. Viridian) at 0x40000000, or when running nested in another Xen
+const HYPERVISOR_SECONDARY_LEAF: u32 = 0x4000_0100;
+/// Xen signature returned in EBX, ECX and EDX of the hypervisor leaves
+const XEN_SIGNATURE: &[u8; 12] = b"XenVMMXenVMM";
+
+/// Check if the CPUID registers contain the Xen signature
+///
+/// # Arguments
+///
+/// * `ebx` - EBX register, first 4 characters of the signature
+/// * `ecx` - ECX register, next 4 characters of the signature
+/// * `edx` - EDX register, last 4 characters of the signature
+///
+/// # Returns
+///
+/// A boolean indicating whether the registers contain the Xen signature
+fn is_xen_signature(ebx: u32, ecx: u32, edx: u32) -> bool {
+    let mut signature = [0u8; 12];
+    signature[..4].copy_from_slice(&ebx.to_le_bytes());
+    signature[4..8].copy_from_slice(&ecx.to_le_bytes());
+    signature[8..].copy_from_slice(&edx.to_le_bytes());
+    &signature == XEN_SIGNATURE
+}
+
+#[technique(
+    name = "Secondary hypervisor signature",
+    description = "Check for the Xen signature at CPUID leaf 0x40000100, used when Xen exposes another hypervisor interface at 0x40000000.
+    Absence of the signature is inconclusive, as Xen usually only uses the base leaf.",
+    os = "all"
+)]
+fn secondary_hypervisor_signature() -> TechniqueResult {
+    let leaf = cpuid!(HYPERVISOR_SECONDARY_LEAF);
+    if is_xen_signature(leaf.ebx, leaf.ecx, leaf.edx) {
+        return Ok(DetectionResult::Detected);
+    }
+
+    Ok(DetectionResult::NotDetected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_xen_signature() {
+        let register = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+
+        assert!(is_xen_signature(
+            register(b"XenV"),
+            register(b"MMXe"),
+            register(b"nVMM")
+        ));
+        // Registers in the wrong order
+        assert!(!is_xen_signature(
+            register(b"XenV"),
+            register(b"nVMM"),
+            register(b"MMXe")
+        ));
+        // Viridian signature
+        assert!(!is_xen_signature(
+            register(b"Micr"),
+            register(b"osof"),
+            register(b"t Hv")
+        ));
+        assert!(!is_xen_signature(0, 0, 0));
+    }
+
+    #[test]
+    fn test_reports_tsc_frequency() {
+        // Xen guest with a 2.4 GHz TSC, Xen reports up to leaf 0x40000005
+        assert!(reports_tsc_frequency(0x4000_0000, 0x4000_0005, 2_400_000));
+        // Xen leaves moved behind a Viridian interface
+        assert!(reports_tsc_frequency(0x4000_0100, 0x4000_0105, 2_400_000));
+        assert!(!reports_tsc_frequency(0x4000_0100, 0x4000_0005, 2_400_000));
+        // Xen without the timing leaf
+        assert!(!reports_tsc_frequency(0x4000_0000, 0x4000_0002, 2_400_000));
+        // Timing leaf implemented but not filled
+        assert!(!reports_tsc_frequency(0x4000_0000, 0x4000_0005, 0));
+        assert!(!reports_tsc_frequency(0x4000_0000, 0x4000_0005, u32::MAX));
+    }
+}
@@ -20,3 +20,321 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 //!
 //! This module implements time-based techniques to detect the presence of the Xen hypervisor
 //! by analyzing timing discrepancies in instruction execution and system operations.
+//!
+//! Timing measurements depend on the load and the frequency scaling of the host, so these
+//! techniques are heuristics with a low confidence. They read the time through a
+//! [`ClockSource`], so that the heuristics can be tested with recorded timestamps.
+
+use std::hint::black_box;
+
+use log::error;
+use raw_cpuid::cpuid;
+use static_init::dynamic;
+
+use crate::clock::{ClockSource, SystemClock};
+use crate::detector::{
+    Confidence, DetectionResult, Technique, TechniqueResult, register_technique,
+};
+
+use xenith_redpill_macros::technique;
+
+/// CPUID leaf timed by the CPUID caching technique (processor brand string)
+const CPUID_TIMING_LEAF: u32 = 0x8000_0002;
+/// Number of timed executions of CPUID and of the control instruction, including the first one
+const TIMING_SAMPLES: usize = 32;
+/// Minimum ratio between the first CPUID execution and the median of the next ones
+const FIRST_CALL_PENALTY_RATIO: u64 = 10;
+/// Minimum ratio between the first-call penalty of CPUID and the one of the control instruction
+const CONTROL_PENALTY_MARGIN: u64 = 4;
+/// Minimum first-call penalty of CPUID in nanoseconds, below the cost of a VM exit
+const MINIMUM_PENALTY_NS: u64 = 1_000;
+
+/// Number of consecutive clock reads sampled by the clock granularity technique
+const CLOCK_SAMPLES: usize = 1_000;
+/// Minimum number of observed clock ticks (non-zero deltas) for the granularity to be meaningful
+const MINIMUM_CLOCK_TICKS: usize = 16;
+/// Tick of the performance counter backing the monotonic clock on Windows 10 and later, which
+/// runs at 10 MHz on physical machines too
+const QPC_TICK_NS: u64 = 100;
+/// Minimum granularity of the clock ticks in nanoseconds for the clock to be considered coarse,
+/// above the [`QPC_TICK_NS`] quantum
+const COARSE_CLOCK_GRANULARITY_NS: u64 = 2 * QPC_TICK_NS;
+
+/// Time the executions of an operation
+///
+/// # Returns
+///
+/// The duration of each execution in nanoseconds, in execution order
+fn time_executions(clock: &dyn ClockSource, mut operation: impl FnMut()) -> Vec<u64> {
+    (0..TIMING_SAMPLES)
+        .map(|_| {
+            let start = clock.now_ns();
+            operation();
+            clock.now_ns().saturating_sub(start)
+        })
+        .collect()
+}
+
+/// Get the penalty of the first execution over the median of the next ones
+///
+/// # Returns
+///
+/// The first duration, the median of the next ones and the penalty, or `None` if there are
+/// less than two samples
+fn first_call_penalty(samples: &[u64]) -> Option<(u64, u64, u64)> {
+    let (first, next) = samples.split_first()?;
+    let mut next = next.to_vec();
+    next.sort_unstable();
+    let median = *next.get(next.len() / 2)?;
+
+    Some((*first, median, first.saturating_sub(median)))
+}
+
+/// Check if the first CPUID execution shows a caching penalty
+///
+/// Under Xen, CPUID traps to the hypervisor, whose emulation path is cold on the first
+/// execution of a leaf. On a physical CPU, the first execution only suffers from cold caches,
+/// which also slow the control instruction down. The penalty is flagged when:
+///
+/// - the first CPUID execution is [`FIRST_CALL_PENALTY_RATIO`] times slower than the median
+///   of the next ones,
+/// - the penalty is at least [`MINIMUM_PENALTY_NS`], so sub-microsecond noise is ignored,
+/// - the penalty is [`CONTROL_PENALTY_MARGIN`] times larger than the one of the control
+///   instruction, so a throttled or loaded CPU slowing everything down is not flagged.
+///
+/// # Arguments
+///
+/// * `cpuid_samples` - Durations of the CPUID executions in nanoseconds, first one included
+/// * `control_samples` - Durations of the control executions in nanoseconds, first one included
+pub fn has_cpuid_first_call_penalty(cpuid_samples: &[u64], control_samples: &[u64]) -> bool {
+    let (Some((first, median, penalty)), Some((_, _, control_penalty))) = (
+        first_call_penalty(cpuid_samples),
+        first_call_penalty(control_samples),
+    ) else {
+        return false;
+    };
+
+    first >= median.saturating_mul(FIRST_CALL_PENALTY_RATIO)
+        && penalty >= MINIMUM_PENALTY_NS
+        && penalty >= control_penalty.saturating_mul(CONTROL_PENALTY_MARGIN)
+}
+
+#[technique(
+    name = "CPUID caching",
+    description = "Check if the first execution of a CPUID leaf is much slower than the next ones compared to a control instruction, which happens when CPUID is emulated by the hypervisor",
+    os = "all",
+    confidence = "low"
+)]
+fn cpuid_caching() -> TechniqueResult {
+    cpuid_caching_with(&SystemClock::new())
+}
+
+/// Run the CPUID caching technique, timing with the given clock
+///
+/// See [`has_cpuid_first_call_penalty`] for the heuristic. CPUID is timed first, then the
+/// control instruction, with two clock reads per execution.
+///
+/// # Arguments
+///
+/// * `clock` - The clock used to time the executions
+pub fn cpuid_caching_with(clock: &dyn ClockSource) -> TechniqueResult {
+    let cpuid_samples = time_executions(clock, || {
+        black_box(cpuid!(black_box(CPUID_TIMING_LEAF)));
+    });
+    let mut counter = 0u64;
+    let control_samples = time_executions(clock, || {
+        counter = black_box(counter.wrapping_add(1));
+    });
+
+    if has_cpuid_first_call_penalty(&cpuid_samples, &control_samples) {
+        return Ok(DetectionResult::Detected);
+    }
+
+    Ok(DetectionResult::NotDetected)
+}
+
+/// Sample the deltas between consecutive reads of the monotonic clock
+///
+/// # Returns
+///
+/// The deltas in nanoseconds, zero when the clock did not tick between two reads
+fn sample_clock_deltas(clock: &dyn ClockSource) -> Vec<u64> {
+    let mut previous = clock.now_ns();
+    (0..CLOCK_SAMPLES)
+        .map(|_| {
+            let now = clock.now_ns();
+            let delta = now.saturating_sub(previous);
+            previous = now;
+            delta
+        })
+        .collect()
+}
+
+/// Greatest common divisor of two numbers
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Check if the observed ticks of the monotonic clock are suspiciously coarse
+///
+/// On physical machines the monotonic clock is derived from the TSC and scaled to
+/// nanoseconds, so the deltas between consecutive reads have no common divisor other than a
+/// few nanoseconds. On Windows, the performance counter quantizes the deltas to 100 ns even on
+/// physical machines, so granularities up to that quantum are not flagged. Emulated or
+/// paravirtualized clocks (e.g. an emulated TSC or a clock
+/// source updated by the hypervisor) tick in fixed steps, which makes every delta a multiple
+/// of a large granularity.
+///
+/// Only the granularity is considered, not the latency of a read: low-power or throttled
+/// CPUs have slow clock reads, but their deltas are still not quantized. Too few ticks are
+/// inconclusive and are not flagged.
+///
+/// # Arguments
+///
+/// * `deltas` - Deltas between consecutive clock reads in nanoseconds, zero for no tick
+pub fn has_coarse_clock_granularity(deltas: &[u64]) -> bool {
+    let ticks = deltas
+        .iter()
+        .copied()
+        .filter(|delta| *delta > 0)
+        .collect::<Vec<u64>>();
+    if ticks.len() < MINIMUM_CLOCK_TICKS {
+        return false;
+    }
+
+    ticks.into_iter().fold(0, gcd) >= COARSE_CLOCK_GRANULARITY_NS
+}
+
+#[technique(
+    name = "Clock granularity",
+    description = "Check if the monotonic clock ticks in coarse fixed steps, which happens with clocks emulated by the hypervisor",
+    os = "all",
+    confidence = "low"
+)]
+fn clock_granularity() -> TechniqueResult {
+    clock_granularity_with(&SystemClock::new())
+}
+
+/// Run the clock granularity technique on the given clock
+///
+/// See [`has_coarse_clock_granularity`] for the heuristic.
+///
+/// # Arguments
+///
+/// * `clock` - The clock to sample
+pub fn clock_granularity_with(clock: &dyn ClockSource) -> TechniqueResult {
+    if has_coarse_clock_granularity(&sample_clock_deltas(clock)) {
+        return Ok(DetectionResult::Detected);
+    }
+
+    Ok(DetectionResult::NotDetected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn test_cpuid_first_call_penalty() {
+        let control = [120, 40, 40, 41, 40];
+
+        // VM exit on the first execution
+        assert!(has_cpuid_first_call_penalty(
+            &[25_000, 1_200, 1_150, 1_300, 1_180],
+            &control
+        ));
+        // Cold caches on a physical CPU
+        assert!(!has_cpuid_first_call_penalty(
+            &[900, 110, 100, 105, 100],
+            &control
+        ));
+        // Throttled CPU, the control instruction suffers from the same penalty
+        assert!(!has_cpuid_first_call_penalty(
+            &[25_000, 1_200, 1_150, 1_300, 1_180],
+            &[20_000, 40, 40, 41, 40]
+        ));
+        assert!(!has_cpuid_first_call_penalty(&[25_000], &control));
+    }
+
+    #[test]
+    fn test_coarse_clock_granularity() {
+        // Clock updated every microsecond
+        let emulated = [0, 0, 1_000, 0, 2_000, 1_000, 0, 1_000].repeat(4);
+        assert!(has_coarse_clock_granularity(&emulated));
+
+        // TSC-based clock, with the slow reads of a low-power CPU
+        let physical = [612, 598, 1_204, 603, 587, 640, 601, 599].repeat(4);
+        assert!(!has_coarse_clock_granularity(&physical));
+
+        // Performance counter of a physical Windows host, quantized to 100ns
+        let qpc = [300, 0, 200, 500, 100, 300, 0, 400].repeat(4);
+        assert!(!has_coarse_clock_granularity(&qpc));
+
+        // Too few ticks to conclude
+        assert!(!has_coarse_clock_granularity(&[0, 4_000, 0, 4_000]));
+        assert!(!has_coarse_clock_granularity(&[]));
+    }
+
+    /// Create a clock timing the executions of CPUID then of the control instruction
+    fn execution_clock(cpuid_durations: [u64; 2], control_durations: [u64; 2]) -> MockClock {
+        let mut intervals = Vec::new();
+        for [first, next] in [cpuid_durations, control_durations] {
+            for i in 0..TIMING_SAMPLES {
+                intervals.push(if i == 0 { first } else { next });
+                // Time between two executions
+                intervals.push(50);
+            }
+        }
+        MockClock::from_intervals(&intervals)
+    }
+
+    #[test]
+    fn test_cpuid_caching_with_mock_clock() {
+        // VM exit on the first execution
+        assert_eq!(
+            cpuid_caching_with(&execution_clock([25_000, 1_200], [120, 40])),
+            Ok(DetectionResult::Detected)
+        );
+        // Cold caches on a physical CPU
+        assert_eq!(
+            cpuid_caching_with(&execution_clock([900, 100], [120, 40])),
+            Ok(DetectionResult::NotDetected)
+        );
+    }
+
+    #[test]
+    fn test_clock_granularity_with_mock_clock() {
+        // Clock updated every microsecond, read every 300ns
+        let emulated = MockClock::new(
+            (0..=CLOCK_SAMPLES as u64)
+                .map(|read| read * 300 / 1_000 * 1_000)
+                .collect(),
+        );
+        assert_eq!(
+            clock_granularity_with(&emulated),
+            Ok(DetectionResult::Detected)
+        );
+
+        // Nanosecond clock, with jittery reads
+        let physical = MockClock::from_intervals(
+            &(0..CLOCK_SAMPLES as u64)
+                .map(|read| 20 + read % 7)
+                .collect::<Vec<u64>>(),
+        );
+        assert_eq!(
+            clock_granularity_with(&physical),
+            Ok(DetectionResult::NotDetected)
+        );
+        // Performance counter of a physical Windows host, read every 250ns
+        let qpc = MockClock::new(
+            (0..=CLOCK_SAMPLES as u64)
+                .map(|read| read * 250 / QPC_TICK_NS * QPC_TICK_NS)
+                .collect(),
+        );
+        assert_eq!(
+            clock_granularity_with(&qpc),
+            Ok(DetectionResult::NotDetected)
+        );
+    }
+}
@@ -12,6 +12,10 @@ struct TechniqueArgs {
     name: String,
     description: String,
     os: String, // todo: enum
+    #[darling(default)]
+    remediation: Option<String>,
+    #[darling(default)]
+    confidence: Option<String>,
 }
 
 pub fn uppercase_first_letter(s: String) -> String {
@@ -30,6 +34,8 @@ pub fn uppercase_first_letter(s: String) -> String {
 /// * `name` - The name of the technique
 /// * `description` - A description of the technique
 /// * `os` - The operating system(s) the technique is compatible with
+/// * `remediation` - Optional hint on how to defeat the technique
+/// * `confidence` - Optional confidence level of the technique, `low`, `medium` (default) or `high`
 ///
 /// # Returns
 ///
@@ -64,6 +70,22 @@ pub fn technique_impl(args: TokenStream, input: TokenStream) -> TokenStream {
     let technique_name = &args.name;
     let technique_description = &args.description;
     let technique_os = &args.os;
+    let technique_remediation = match &args.remediation {
+        Some(remediation) => quote! { Some(#remediation) },
+        None => quote! { None },
+    };
+
+    let technique_confidence = match args.confidence.as_deref() {
+        Some("low") => quote! { Confidence::Low },
+        Some("medium") | None => quote! { Confidence::Medium },
+        Some("high") => quote! { Confidence::High },
+        Some(_) => {
+            return TokenStream::from(
+                Error::custom("Invalid confidence, choose from 'low', 'medium' or 'high'")
+                    .write_errors(),
+            )
+        }
+    };
 
     let function_name_as_camel_case = function_name.to_string().to_case(Case::Camel);
     let struct_name = syn::Ident::new(
@@ -109,6 +131,16 @@ pub fn technique_impl(args: TokenStream, input: TokenStream) -> TokenStream {
             fn description(&self) -> &'static str {
                 #technique_description
             }
+            fn remediation(&self) -> Option<&'static str> {
+                #technique_remediation
+            }
+            fn confidence(&self) -> Confidence {
+                #technique_confidence
+            }
+            fn category(&self) -> &'static str {
+                // Techniques are grouped by module, e.g. `techniques::signature`
+                module_path!().rsplit("::").next().unwrap_or_default()
+            }
             fn execute(&self) -> TechniqueResult {
                 #function_name()
             }
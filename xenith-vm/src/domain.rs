@@ -28,6 +28,7 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 mod boot;
 mod devices;
 mod events;
+mod memory;
 mod network;
 mod processor;
 mod time;
@@ -35,19 +36,25 @@ mod time;
 pub use boot::*;
 pub use devices::*;
 pub use events::*;
+pub use memory::*;
 pub use network::*;
 pub use processor::*;
 pub use time::*;
 
 use crate::XlConfiguration;
+use crate::error::TemplateError;
 
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
 use std::fmt::Display;
 
 /// Represents the type of Xen virtual machine
 ///
 /// ⚠️ Even though PV and PVH are supported by Xen and listed here, they are not supported by Xenith.
-/// Those are kept here for future compatibility.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// Those are kept here for future compatibility, and can only be used with the `pv-support`
+/// feature.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum DomainType {
     /// Hardware Virtual Machine : This is a full virtualization technique that allows the guest
     /// operating system to run on the virtual machine without any modifications. It provides better
@@ -86,7 +93,7 @@ impl XlConfiguration for DomainType {
 
 /// Represents the type of VGA console to use for the guest
 /// The VGA console is used to display the guest operating system's graphical output.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum GuestConsole {
     /// Simple DirectMedia Layer (SDL) : This is a cross-platform multimedia library that provides
     /// low-level access to audio, keyboard, mouse, and display hardware. It is used to create
@@ -96,6 +103,51 @@ pub enum GuestConsole {
     /// Virtual Network Computing (VNC) : This is a remote desktop protocol that allows you to view
     /// and interact with the guest operating system's graphical output over a network connection.
     Vnc,
+    /// Simple Protocol for Independent Computing Environments (SPICE) : This is a remote display
+    /// protocol offering better desktop performance than VNC, especially for Windows guests.
+    ///
+    /// ⚠️ Only available for HVM guests.
+    Spice {
+        /// Port the SPICE server listens on for plain connections
+        port: Option<u16>,
+        /// Port the SPICE server listens on for TLS connections
+        tls_port: Option<u16>,
+        /// Password required to connect to the SPICE server
+        password: Option<String>,
+        /// Allow clients to connect without a password
+        disable_ticketing: bool,
+    },
+}
+
+impl GuestConsole {
+    /// Validate the guest console configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if a SPICE password is set while ticketing
+    /// is disabled, as the password would be silently ignored, or if the password contains a
+    /// double quote or a line break, which can't be escaped in `xl.cfg`
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        if let GuestConsole::Spice {
+            password: Some(password),
+            disable_ticketing,
+            ..
+        } = self
+        {
+            if *disable_ticketing {
+                return Err(TemplateError::Validation(
+                    "a SPICE password can't be set when ticketing is disabled".to_string(),
+                ));
+            }
+            if password.contains(['"', '\n', '\r']) {
+                return Err(TemplateError::Validation(
+                    "SPICE password must not contain '\"' or line breaks".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Display for GuestConsole {
@@ -103,19 +155,43 @@ impl Display for GuestConsole {
         match self {
             GuestConsole::Sdl => write!(f, "sdl"),
             GuestConsole::Vnc => write!(f, "vnc"),
+            GuestConsole::Spice { .. } => write!(f, "spice"),
         }
     }
 }
 
 impl XlConfiguration for GuestConsole {
+    // sdl=BOOLEAN, vnc=BOOLEAN or spice=BOOLEAN followed by the spice options
     fn xl_config(&self) -> String {
-        // TODO: Implement this following xl.cfg manual
-        unimplemented!()
+        let mut config = vec![format!("{} = 1", self)];
+
+        if let GuestConsole::Spice {
+            port,
+            tls_port,
+            password,
+            disable_ticketing,
+        } = self
+        {
+            if let Some(port) = port {
+                config.push(format!("spiceport = {}", port));
+            }
+            if let Some(tls_port) = tls_port {
+                config.push(format!("spicetls_port = {}", tls_port));
+            }
+            if let Some(password) = password {
+                config.push(format!("spicepasswd = \"{}\"", password));
+            }
+            if *disable_ticketing {
+                config.push("spicedisable_ticketing = 1".to_string());
+            }
+        }
+
+        config.join("\n")
     }
 }
 
 /// Represents the number of virtual CPUs to allocate to the virtual machine
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct VirtualCpuNumber(pub u8);
 
 impl Display for VirtualCpuNumber {
@@ -131,7 +207,7 @@ impl XlConfiguration for VirtualCpuNumber {
 }
 
 /// Represents the number of maximum virtual CPUs to allocate to the virtual machine
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct MaximumVirtualCpuNumber(pub u8);
 
 impl Display for MaximumVirtualCpuNumber {
@@ -148,7 +224,7 @@ impl XlConfiguration for MaximumVirtualCpuNumber {
 
 /// Represents the initial memory capacity of the virtual machine
 /// This is the amount of memory that will be allocated to the virtual machine when it starts.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct MemoryCapacity(pub u64);
 
 impl Display for MemoryCapacity {
@@ -165,7 +241,7 @@ impl XlConfiguration for MemoryCapacity {
 
 /// Represents the maximum memory capacity of the virtual machine
 /// This is the maximum amount of memory that the virtual machine can use.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct MaximumMemoryCapacity(pub u64);
 
 impl Display for MaximumMemoryCapacity {
@@ -181,7 +257,7 @@ impl XlConfiguration for MaximumMemoryCapacity {
 }
 
 /// Specifies if the domain should have access to virtualization extensions
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct NestedHvm(pub bool);
 
 impl Display for NestedHvm {
@@ -196,8 +272,52 @@ impl XlConfiguration for NestedHvm {
     }
 }
 
+/// Specifies if the Microsoft Hyper-V compatible enlightenments (viridian) are exposed to
+/// the domain. They improve the performance and the stability of Windows guests.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct Viridian(pub bool);
+
+impl Display for Viridian {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "viridian = {}", self.0 as u8)
+    }
+}
+
+impl XlConfiguration for Viridian {
+    fn xl_config(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Guest operating systems with a [`Domain`] preset, see [`Domain::preset`]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum GuestOs {
+    Debian12,
+    Ubuntu2404,
+    Windows10,
+    Windows11,
+}
+
+impl GuestOs {
+    /// Check if the guest operating system is Windows
+    pub fn is_windows(&self) -> bool {
+        matches!(self, GuestOs::Windows10 | GuestOs::Windows11)
+    }
+}
+
+impl Display for GuestOs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GuestOs::Debian12 => write!(f, "debian-12"),
+            GuestOs::Ubuntu2404 => write!(f, "ubuntu-24.04"),
+            GuestOs::Windows10 => write!(f, "windows-10"),
+            GuestOs::Windows11 => write!(f, "windows-11"),
+        }
+    }
+}
+
 /// Represents the name of the virtual machine
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct DomainName(pub String);
 
 impl Display for DomainName {
@@ -206,6 +326,26 @@ impl Display for DomainName {
     }
 }
 
+impl DomainName {
+    /// Validate the domain name
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if the name is empty or contains characters other
+    /// than ASCII alphanumerics, `-`, `_` and `.`, which could break the `xl` specs it is used in
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+        if self.0.is_empty() || !self.0.chars().all(valid) {
+            return Err(TemplateError::Validation(format!(
+                "invalid domain name \"{}\"",
+                self.0
+            )));
+        }
+
+        Ok(())
+    }
+}
+
 impl XlConfiguration for DomainName {
     fn xl_config(&self) -> String {
         self.to_string()
@@ -218,7 +358,7 @@ impl XlConfiguration for DomainName {
 /// commonly used options for creating a virtual machine.
 ///
 /// See `man xl.cfg` for more information.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct Domain {
     /// Name of the virtual machine
     pub name: DomainName,
@@ -230,20 +370,35 @@ pub struct Domain {
     /// vcpus=N is less than maxvcpus=M then the first N vCPUs will be created online and
     /// the remainder will be created offline.
     pub maximum_virtual_cpus: MaximumVirtualCpuNumber,
+    /// CPU topology presented to the guest. The number of logical processors must match
+    /// the maximum number of vCPUs. Xen default topology is used when `None`.
+    pub cpu_topology: Option<CpuTopology>,
+    /// CPU vendor and brand presented to the guest. Host CPU model is used when `None`.
+    pub cpu_model: Option<CpuModel>,
     /// Initial memory allocation in mega bytes
     pub memory: MemoryCapacity,
     /// Maximum memory size in mega bytes
     /// If this is greater than `memory' then the slack will start ballooned
     /// (this assumes guest kernel support for ballooning)
     pub maximum_memory: MaximumMemoryCapacity,
+    /// Backing used for the guest memory, e.g. hugepages
+    ///
+    /// ⚠️ Only the default backing is supported, see [`MemoryBacking`].
+    pub memory_backing: MemoryBacking,
     /// Disk devices attached to the virtual machine
     pub disks: DiskDevices,
     /// List of network interfaces attached to the virtual machine
     pub network_interfaces: NetworkInterfaces,
     /// Actions to take when a domain event occurs
     pub domain_actions: DomainActions,
+    /// Watchdog device of the domain, none by default
+    pub watchdog: Option<Watchdog>,
     /// The firmware to use for the virtual machine
     pub firmware: Firmware,
+    /// Kernel loaded by the toolstack, booted instead of the firmware. Required for PV guests.
+    ///
+    /// ⚠️ Only available with the `pv-support` feature.
+    pub direct_kernel_boot: Option<DirectKernelBoot>,
     /// Specifies the emulated virtual device to boot from.
     ///
     /// **Note**: multiple options can be given and will be attempted in the order they are
@@ -252,6 +407,29 @@ pub struct Domain {
     pub boot_devices: BootDevices,
     /// Specifies the type of emulated disk controller to use.
     pub emulated_disk_controller: EmulatedDiskControllerType,
+    /// Specifies the device model emulating the hardware of the domain.
+    ///
+    /// ⚠️ Only available for HVM guests.
+    pub device_model_version: DeviceModelVersion,
+    /// Specifies the emulated sound hardware, none by default.
+    ///
+    /// ⚠️ Only available for HVM guests.
+    pub sound_hardware: SoundHardware,
+    /// Specifies the emulated graphics card. The device model default is used when `None`.
+    ///
+    /// ⚠️ Only available for HVM guests.
+    pub video_adapter: Option<VideoAdapter>,
+    /// Graphical console used to display the guest output. No graphical console is
+    /// provided when `None`.
+    pub console: Option<GuestConsole>,
+    /// Attach an emulated USB tablet as pointer device. Absolute pointing keeps the guest
+    /// cursor aligned with the client one, whereas the default PS/2 mouse drifts over VNC
+    /// and SDL, which makes desktop guests (e.g. Windows) hardly usable.
+    ///
+    /// When `None`, the tablet is attached to HVM guests with an SDL or VNC console.
+    ///
+    /// ⚠️ Only available for HVM guests.
+    pub usb_tablet: Option<bool>,
     /// Alternative p2m (altp2m) allows external monitoring of guest memory
     /// by maintaining multiple physical to machine (p2m) memory mappings.
     /// Specifies the access mode to the alternate-p2m capability.
@@ -269,11 +447,600 @@ pub struct Domain {
     /// virtualisation extensions (e.g. Windows XP compatibility mode on more modern
     /// Windows OS).
     pub nested_hvm: NestedHvm,
+    /// Expose the Hyper-V enlightenments to the guest, disabled by default.
+    ///
+    /// ⚠️ Only available for HVM guests.
+    pub viridian: Viridian,
     /// SMBIOS information for the domain
     pub smbios: SmBios,
     /// Specifies how the TSC (Time Stamp Counter) should be provided to the
     /// guest.
     pub tsc_mode: TimeStampCounterMode,
+    /// Offset of the emulated real time clock, UTC by default.
+    pub clock_offset: ClockOffset,
+    /// Additional `xl.cfg` lines appended verbatim at the end of the configuration, in
+    /// insertion order. Each entry must be a full `key = value` line, which allows to set
+    /// options not modelled by Xenith yet. Blank entries are skipped.
+    pub extra_config: Vec<String>,
+    /// Arbitrary key/value labels attached to the domain, e.g. `owner` or `environment`.
+    /// They are stored with the domain description but not rendered in the `xl.cfg`.
+    pub labels: BTreeMap<String, String>,
+}
+
+/// Represents the change of a field between two domains, see [`Domain::diff`]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct FieldChange {
+    /// Name of the changed field
+    pub field: &'static str,
+    /// Representation of the field before the change, empty if it is unset
+    pub old: String,
+    /// Representation of the field after the change, empty if it is unset
+    pub new: String,
+}
+
+impl Display for FieldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let representation = |value: &str| match value {
+            "" => "(none)".to_string(),
+            value => value.to_string(),
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            representation(&self.old),
+            representation(&self.new)
+        )
+    }
+}
+
+impl Domain {
+    /// Header written at the top of every generated configuration file
+    pub const CONFIG_HEADER: &str = "\
+# Configuration file for a Xenith domain
+# This file was auto-generated by xenith-vm
+# --------------------------------------
+";
+
+    /// Filter the xl configuration of an option only available for HVM guests
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The xl configuration of the option
+    ///
+    /// # Returns
+    ///
+    /// The xl configuration of the option if the domain is an HVM guest, an empty string otherwise
+    pub(crate) fn hvm_xl_config(&self, config: String) -> String {
+        match self.r#type {
+            DomainType::Hvm => config,
+            _ => String::new(),
+        }
+    }
+}
+
+impl Domain {
+    /// Create a domain with sensible defaults for a guest operating system
+    ///
+    /// All presets are HVM guests booting with UEFI on an AHCI controller, with a single
+    /// network interface:
+    ///
+    /// - Linux guests get a PV-only interface, handled by the xen-netfront driver of their
+    ///   kernel, and a RTC in UTC.
+    /// - Windows guests get an emulated e1000 interface, as the Xen PV drivers are not
+    ///   installed by default, the viridian enlightenments and a RTC in local time.
+    ///
+    /// The name, memory, vCPUs and disks are left to the caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `os` - The guest operating system
+    pub fn preset(os: GuestOs) -> Domain {
+        let network_interface = if os.is_windows() {
+            NetworkInterface {
+                r#type: NetworkInterfaceType::IoEmu,
+                model: Some(NetworkInterfaceModel::E1000),
+                ..Default::default()
+            }
+        } else {
+            NetworkInterface {
+                r#type: NetworkInterfaceType::Vif,
+                model: None,
+                ..Default::default()
+            }
+        };
+
+        Domain {
+            r#type: DomainType::Hvm,
+            firmware: Firmware::Uefi,
+            emulated_disk_controller: EmulatedDiskControllerType::Ahci,
+            network_interfaces: NetworkInterfaces(vec![network_interface]),
+            viridian: Viridian(os.is_windows()),
+            clock_offset: if os.is_windows() {
+                ClockOffset::LocalTime
+            } else {
+                ClockOffset::Utc
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Validate the domain configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `check_bridges` - Check that the bridges of the bridged network interfaces exist on
+    ///   the host. This should be disabled when generating a configuration for another host.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError`] if the domain type, boot options or memory backing are not
+    /// supported, if the initial memory or vCPUs exceed their maximum, if a device is not
+    /// emulated by the device model, or if the domain disks, event actions, console, watchdog,
+    /// video adapter, CPU topology, CPU model, SMBIOS strings, TSC mode or network interfaces
+    /// are invalid
+    pub fn validate(&self, check_bridges: bool) -> Result<(), TemplateError> {
+        self.validate_boot()?;
+        self.validate_resources()?;
+        self.memory_backing.validate()?;
+        self.validate_device_model()?;
+        self.disks.validate()?;
+        if self.r#type == DomainType::Hvm {
+            self.disks
+                .validate_controller(&self.emulated_disk_controller)?;
+        }
+        self.effective_domain_actions().validate()?;
+        self.tsc_mode.validate()?;
+        if let Some(console) = &self.console {
+            console.validate()?;
+        }
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.validate()?;
+        }
+        if let Some(video_adapter) = &self.video_adapter {
+            video_adapter.validate()?;
+        }
+        if let Some(cpu_topology) = &self.cpu_topology {
+            cpu_topology.validate(&self.maximum_virtual_cpus)?;
+        }
+        if let Some(cpu_model) = &self.cpu_model {
+            cpu_model.validate()?;
+        }
+        self.smbios.validate()?;
+        for network_interface in &self.network_interfaces.0 {
+            network_interface.validate()?;
+        }
+        if check_bridges {
+            for network_interface in &self.network_interfaces.0 {
+                if network_interface.mode == NetworkMode::Bridge {
+                    network_interface.validate_bridge()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the initial memory and vCPUs against their maximum
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if `memory` is greater than `maxmem` or if
+    /// `vcpus` is greater than `maxvcpus`, which `xl` refuses
+    fn validate_resources(&self) -> Result<(), TemplateError> {
+        if self.memory.0 > self.maximum_memory.0 {
+            return Err(TemplateError::Validation(format!(
+                "memory ({} MB) can't be greater than maxmem ({} MB)",
+                self.memory.0, self.maximum_memory.0
+            )));
+        }
+        if self.virtual_cpus.0 > self.maximum_virtual_cpus.0 {
+            return Err(TemplateError::Validation(format!(
+                "vcpus ({}) can't be greater than maxvcpus ({})",
+                self.virtual_cpus.0, self.maximum_virtual_cpus.0
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Validate the domain type and its boot options
+    ///
+    /// PV and PVH domains, as well as direct kernel boot, are only allowed with the
+    /// `pv-support` feature. PV domains then require a direct kernel boot, as Xenith doesn't
+    /// configure PV bootloaders.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if the domain type or boot options are not
+    /// supported
+    fn validate_boot(&self) -> Result<(), TemplateError> {
+        if !cfg!(feature = "pv-support") {
+            if self.r#type != DomainType::Hvm {
+                return Err(TemplateError::Validation(format!(
+                    "{} domains are not supported, enable the pv-support feature",
+                    self.r#type
+                )));
+            }
+            if self.direct_kernel_boot.is_some() {
+                return Err(TemplateError::Validation(
+                    "direct kernel boot is not supported, enable the pv-support feature"
+                        .to_string(),
+                ));
+            }
+        }
+
+        match &self.direct_kernel_boot {
+            Some(direct_kernel_boot) => direct_kernel_boot.validate(),
+            None if self.r#type == DomainType::Pv => Err(TemplateError::Validation(
+                "pv domains require a direct kernel boot".to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Validate the devices against the device model
+    ///
+    /// The traditional device model doesn't emulate AHCI controllers and only boots ROMBIOS,
+    /// the UEFI and SeaBIOS firmwares require upstream QEMU.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if a device of an HVM domain is not emulated by
+    /// its device model
+    fn validate_device_model(&self) -> Result<(), TemplateError> {
+        if self.r#type != DomainType::Hvm
+            || self.device_model_version != DeviceModelVersion::QemuXenTraditional
+        {
+            return Ok(());
+        }
+
+        if self.emulated_disk_controller == EmulatedDiskControllerType::Ahci {
+            return Err(TemplateError::Validation(format!(
+                "the {} disk controller requires the {} device model",
+                EmulatedDiskControllerType::Ahci,
+                DeviceModelVersion::QemuXen
+            )));
+        }
+
+        let upstream_firmware = matches!(
+            self.firmware,
+            Firmware::Uefi | Firmware::Ovmf | Firmware::Seabios
+        );
+        if self.direct_kernel_boot.is_none() && upstream_firmware {
+            return Err(TemplateError::Validation(format!(
+                "the {} firmware requires the {} device model",
+                self.firmware,
+                DeviceModelVersion::QemuXen
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Compare the fields of two domains
+    ///
+    /// Fields are compared on their `xl` representation, whatever the domain type, so a
+    /// change which doesn't affect the rendered configuration, e.g. the size of a disk, is
+    /// not reported. Fields without an `xl` representation (USB tablet, labels) are compared
+    /// on their value.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The domain after the changes
+    ///
+    /// # Returns
+    ///
+    /// The changed fields, in declaration order
+    pub fn diff(&self, other: &Domain) -> Vec<FieldChange> {
+        self.field_representations()
+            .into_iter()
+            .zip(other.field_representations())
+            .filter(|((_, old), (_, new))| old != new)
+            .map(|((field, old), (_, new))| FieldChange { field, old, new })
+            .collect()
+    }
+
+    /// Get the representation of each field, as compared by [`Domain::diff`]
+    fn field_representations(&self) -> Vec<(&'static str, String)> {
+        fn optional<T: XlConfiguration>(value: &Option<T>) -> String {
+            value.as_ref().map(T::xl_config).unwrap_or_default()
+        }
+
+        let labels = self
+            .labels
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<String>>();
+
+        vec![
+            ("name", self.name.xl_config()),
+            ("type", self.r#type.xl_config()),
+            ("virtual_cpus", self.virtual_cpus.xl_config()),
+            (
+                "maximum_virtual_cpus",
+                self.maximum_virtual_cpus.xl_config(),
+            ),
+            ("cpu_topology", optional(&self.cpu_topology)),
+            ("cpu_model", optional(&self.cpu_model)),
+            ("memory", self.memory.xl_config()),
+            ("maximum_memory", self.maximum_memory.xl_config()),
+            ("memory_backing", self.memory_backing.to_string()),
+            ("disks", self.disks.xl_config()),
+            ("network_interfaces", self.network_interfaces.xl_config()),
+            ("domain_actions", self.domain_actions.xl_config()),
+            ("watchdog", optional(&self.watchdog)),
+            ("firmware", self.firmware.xl_config()),
+            ("direct_kernel_boot", optional(&self.direct_kernel_boot)),
+            ("boot_devices", self.boot_devices.xl_config()),
+            (
+                "emulated_disk_controller",
+                self.emulated_disk_controller.xl_config(),
+            ),
+            (
+                "device_model_version",
+                self.device_model_version.xl_config(),
+            ),
+            ("sound_hardware", self.sound_hardware.xl_config()),
+            ("video_adapter", optional(&self.video_adapter)),
+            ("console", optional(&self.console)),
+            (
+                "usb_tablet",
+                self.usb_tablet
+                    .map(|usb_tablet| usb_tablet.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("alternate_p2m", self.alternate_p2m.xl_config()),
+            ("nested_hvm", self.nested_hvm.xl_config()),
+            ("viridian", self.viridian.xl_config()),
+            ("smbios", self.smbios.xl_config()),
+            ("tsc_mode", self.tsc_mode.xl_config()),
+            ("clock_offset", self.clock_offset.xl_config()),
+            ("extra_config", self.extra_xl_config()),
+            ("labels", labels.join(", ")),
+        ]
+    }
+
+    /// Estimate the size of the rendered `xl.cfg`
+    ///
+    /// # Returns
+    ///
+    /// The size in bytes of the configuration generated by [`Domain::xl_config`]
+    pub fn estimate_config_size(&self) -> usize {
+        self.xl_config().len()
+    }
+
+    /// Get the value of a label
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the label
+    ///
+    /// # Returns
+    ///
+    /// The value of the label, or `None` if it is not set
+    pub fn label(&self, key: &str) -> Option<&str> {
+        self.labels.get(key).map(String::as_str)
+    }
+
+    /// Set a label, replacing its previous value
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the label
+    /// * `value` - The value of the label
+    ///
+    /// # Returns
+    ///
+    /// The previous value of the label, if any
+    pub fn set_label(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Option<String> {
+        self.labels.insert(key.into(), value.into())
+    }
+
+    /// Check if an emulated USB tablet should be attached to the domain
+    ///
+    /// # Returns
+    ///
+    /// The value of [`Domain::usb_tablet`] if set, otherwise whether the domain is an HVM
+    /// guest with an SDL or VNC console
+    pub fn usb_tablet_enabled(&self) -> bool {
+        self.usb_tablet.unwrap_or(
+            self.r#type == DomainType::Hvm
+                && matches!(self.console, Some(GuestConsole::Sdl | GuestConsole::Vnc)),
+        )
+    }
+
+    /// Get the actions to take when a domain event occurs
+    ///
+    /// # Returns
+    ///
+    /// The [`Domain::domain_actions`], with `on_watchdog` replaced by the action of the
+    /// Xen watchdog if one is configured
+    pub fn effective_domain_actions(&self) -> DomainActions {
+        let mut domain_actions = self.domain_actions.clone();
+        if let Some(
+            watchdog @ Watchdog {
+                model: WatchdogModel::Xen,
+                ..
+            },
+        ) = &self.watchdog
+        {
+            domain_actions.on_watchdog = watchdog.action.clone();
+        }
+        domain_actions
+    }
+
+    /// Generate the CPUID configuration of the domain
+    ///
+    /// # Arguments
+    ///
+    /// * `dialect` - The dialect of the rendered configuration, the hypervisor leaves are
+    ///   only rendered with [`XlDialect::Xend`]
+    ///
+    /// # Returns
+    ///
+    /// The `cpuid` option, or an empty string if the CPUID is not overridden
+    pub(crate) fn cpuid_xl_config(&self, dialect: XlDialect) -> String {
+        let mut policies = Vec::new();
+        if let Some(cpu_model) = &self.cpu_model {
+            policies.extend(cpu_model.cpuid_policies());
+            if dialect == XlDialect::Xend {
+                policies.extend(cpu_model.hypervisor_cpuid_policies());
+            }
+        }
+        if let Some(cpu_topology) = &self.cpu_topology {
+            policies.extend(cpu_topology.cpuid_policies());
+        }
+        cpuid_xl_config(&policies)
+    }
+
+    /// Generate the boot configuration of the domain
+    ///
+    /// # Returns
+    ///
+    /// The direct kernel boot options if set, the firmware otherwise
+    pub(crate) fn boot_xl_config(&self) -> String {
+        match &self.direct_kernel_boot {
+            Some(direct_kernel_boot) => direct_kernel_boot.xl_config(),
+            None => self.firmware.xl_config(),
+        }
+    }
+
+    /// Generate the video adapter configuration of the domain
+    ///
+    /// # Returns
+    ///
+    /// The video adapter configuration, or an empty string if the device model default is used
+    pub(crate) fn video_adapter_xl_config(&self) -> String {
+        self.video_adapter
+            .as_ref()
+            .map(|video_adapter| self.hvm_xl_config(video_adapter.xl_config()))
+            .unwrap_or_default()
+    }
+
+    /// Generate the watchdog device configuration of the domain
+    ///
+    /// # Returns
+    ///
+    /// The watchdog device configuration, or an empty string if the domain has no
+    /// emulated watchdog
+    pub(crate) fn watchdog_xl_config(&self) -> String {
+        self.watchdog
+            .as_ref()
+            .map(|watchdog| self.hvm_xl_config(watchdog.xl_config()))
+            .unwrap_or_default()
+    }
+
+    /// Generate the USB devices configuration of the domain
+    ///
+    /// # Returns
+    ///
+    /// The `usbdevice` key if a USB tablet is attached, an empty string otherwise
+    pub(crate) fn usb_devices_xl_config(&self) -> String {
+        match self.usb_tablet_enabled() {
+            true => self.hvm_xl_config("usbdevice = \"tablet\"".to_string()),
+            false => String::new(),
+        }
+    }
+
+    /// Generate the additional configuration lines of the domain
+    ///
+    /// # Returns
+    ///
+    /// The non-blank [`Domain::extra_config`] lines, separated by newlines
+    pub(crate) fn extra_xl_config(&self) -> String {
+        self.extra_config
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+}
+
+impl XlConfiguration for Domain {
+    /// Generate the full xl configuration of the domain, without going through the
+    /// templating engine (see [`crate::templating::DomainTemplate`]).
+    ///
+    /// Options are grouped in sections, in the same order as the default template,
+    /// and options rendering to an empty string (as well as empty sections) are skipped.
+    fn xl_config(&self) -> String {
+        let sections = [
+            (
+                "Generic domain configuration",
+                vec![
+                    self.name.xl_config(),
+                    self.r#type.xl_config(),
+                    format!("{} # in MB", self.memory.xl_config()),
+                    format!("{} # in MB", self.maximum_memory.xl_config()),
+                    self.nested_hvm.xl_config(),
+                    self.hvm_xl_config(self.viridian.xl_config()),
+                ],
+            ),
+            (
+                "Boot",
+                vec![
+                    self.boot_xl_config(),
+                    self.hvm_xl_config(self.boot_devices.xl_config()),
+                ],
+            ),
+            (
+                "Devices",
+                vec![
+                    self.disks.xl_config(),
+                    self.hvm_xl_config(self.emulated_disk_controller.xl_config()),
+                    self.hvm_xl_config(self.device_model_version.xl_config()),
+                    self.hvm_xl_config(self.sound_hardware.xl_config()),
+                    self.video_adapter_xl_config(),
+                    self.usb_devices_xl_config(),
+                    self.watchdog_xl_config(),
+                ],
+            ),
+            (
+                "Console",
+                vec![
+                    self.console
+                        .as_ref()
+                        .map(|console| console.xl_config())
+                        .unwrap_or_default(),
+                ],
+            ),
+            ("Network", vec![self.network_interfaces.xl_config()]),
+            ("Events", vec![self.effective_domain_actions().xl_config()]),
+            (
+                "Processor",
+                vec![
+                    self.virtual_cpus.xl_config(),
+                    self.maximum_virtual_cpus.xl_config(),
+                    self.cpuid_xl_config(XlDialect::Libxl),
+                    self.alternate_p2m.xl_config(),
+                    self.smbios.xl_config(),
+                ],
+            ),
+            (
+                "Time",
+                vec![self.tsc_mode.xl_config(), self.clock_offset.xl_config()],
+            ),
+            ("Extra configuration", vec![self.extra_xl_config()]),
+        ];
+
+        let mut config = Domain::CONFIG_HEADER.to_string();
+        for (title, options) in sections {
+            if options.iter().all(|option| option.is_empty()) {
+                continue;
+            }
+
+            config.push_str(&format!("\n# {}\n", title));
+            for option in options.iter().filter(|option| !option.is_empty()) {
+                config.push_str(option);
+                config.push('\n');
+            }
+        }
+        config
+    }
 }
 
 #[cfg(test)]
@@ -301,9 +1068,82 @@ mod tests {
     }
 
     #[test]
-    #[should_panic] // TODO: Implement this
     fn test_guest_console_xl_config() {
-        GuestConsole::Sdl.xl_config();
+        assert_eq!(GuestConsole::Sdl.xl_config(), "sdl = 1");
+        assert_eq!(GuestConsole::Vnc.xl_config(), "vnc = 1");
+    }
+
+    #[test]
+    fn test_spice_guest_console_display() {
+        let console = GuestConsole::Spice {
+            port: Some(5900),
+            tls_port: None,
+            password: None,
+            disable_ticketing: true,
+        };
+        assert_eq!(console.to_string(), "spice");
+    }
+
+    #[test]
+    fn test_spice_guest_console_xl_config() {
+        let console = GuestConsole::Spice {
+            port: Some(5900),
+            tls_port: Some(5901),
+            password: Some("xenith".to_string()),
+            disable_ticketing: false,
+        };
+        assert_eq!(
+            console.xl_config(),
+            "spice = 1\nspiceport = 5900\nspicetls_port = 5901\nspicepasswd = \"xenith\""
+        );
+
+        let console = GuestConsole::Spice {
+            port: Some(5900),
+            tls_port: None,
+            password: None,
+            disable_ticketing: true,
+        };
+        assert_eq!(
+            console.xl_config(),
+            "spice = 1\nspiceport = 5900\nspicedisable_ticketing = 1"
+        );
+    }
+
+    #[test]
+    fn test_spice_guest_console_validate() {
+        assert!(GuestConsole::Vnc.validate().is_ok());
+
+        let console = GuestConsole::Spice {
+            port: Some(5900),
+            tls_port: None,
+            password: Some("xenith".to_string()),
+            disable_ticketing: true,
+        };
+        assert!(matches!(
+            console.validate(),
+            Err(TemplateError::Validation(_))
+        ));
+
+        let console = GuestConsole::Spice {
+            port: Some(5900),
+            tls_port: None,
+            password: Some("xenith".to_string()),
+            disable_ticketing: false,
+        };
+        assert!(console.validate().is_ok());
+
+        for password in ["xen\"ith", "xen\nith", "xen\rith"] {
+            let console = GuestConsole::Spice {
+                port: Some(5900),
+                tls_port: None,
+                password: Some(password.to_string()),
+                disable_ticketing: false,
+            };
+            assert!(matches!(
+                console.validate(),
+                Err(TemplateError::Validation(e)) if e == "SPICE password must not contain '\"' or line breaks"
+            ));
+        }
     }
 
     #[test]
@@ -358,6 +1198,186 @@ mod tests {
         assert_eq!(NestedHvm(false).xl_config(), "nestedhvm = 0");
     }
 
+    #[test]
+    fn test_domain_estimate_config_size() {
+        let mut domain = Domain::default();
+        let size = domain.estimate_config_size();
+        assert_eq!(size, domain.xl_config().len());
+
+        domain.smbios.oems = Some(vec!["A".repeat(63); 65]);
+        assert!(domain.estimate_config_size() > size + 64 * 63);
+        assert!(matches!(
+            domain.validate(false),
+            Err(TemplateError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_viridian_xl_config() {
+        assert_eq!(Viridian(true).xl_config(), "viridian = 1");
+        assert_eq!(Viridian(false).xl_config(), "viridian = 0");
+    }
+
+    #[test]
+    fn test_domain_device_model_version() {
+        let mut domain = Domain {
+            name: DomainName("legacy".to_string()),
+            device_model_version: DeviceModelVersion::QemuXenTraditional,
+            ..Default::default()
+        };
+        assert!(matches!(
+            domain.validate(false),
+            Err(TemplateError::Validation(e)) if e == "the ahci disk controller requires the qemu-xen device model"
+        ));
+
+        domain.emulated_disk_controller = EmulatedDiskControllerType::Ide;
+        assert!(matches!(
+            domain.validate(false),
+            Err(TemplateError::Validation(e)) if e == "the uefi firmware requires the qemu-xen device model"
+        ));
+
+        domain.firmware = Firmware::Rombios;
+        assert!(domain.validate(false).is_ok());
+        assert!(
+            domain
+                .xl_config()
+                .contains("\ndevice_model_version = \"qemu-xen-traditional\"\n")
+        );
+
+        // Disks can't be explicitly attached to another controller
+        domain.disks.push(Disk {
+            virtual_device: "xvda".to_string(),
+            controller: Some(EmulatedDiskControllerType::Ahci),
+            ..Default::default()
+        });
+        assert!(matches!(
+            domain.validate(false),
+            Err(TemplateError::Validation(e)) if e == "disk xvda can't be attached to the ahci controller, xl attaches every disk to the ide controller of the domain"
+        ));
+    }
+
+    #[test]
+    fn test_domain_validate_memory() {
+        let mut domain = Domain {
+            name: DomainName("xenith".to_string()),
+            memory: MemoryCapacity(2048),
+            maximum_memory: MaximumMemoryCapacity(4096),
+            ..Default::default()
+        };
+        assert!(domain.validate(false).is_ok());
+
+        domain.maximum_memory = MaximumMemoryCapacity(1024);
+        assert!(matches!(
+            domain.validate(false),
+            Err(TemplateError::Validation(e)) if e == "memory (2048 MB) can't be greater than maxmem (1024 MB)"
+        ));
+    }
+
+    #[test]
+    fn test_domain_validate_virtual_cpus() {
+        let mut domain = Domain {
+            name: DomainName("xenith".to_string()),
+            virtual_cpus: VirtualCpuNumber(2),
+            maximum_virtual_cpus: MaximumVirtualCpuNumber(4),
+            ..Default::default()
+        };
+        assert!(domain.validate(false).is_ok());
+
+        domain.maximum_virtual_cpus = MaximumVirtualCpuNumber(1);
+        assert!(matches!(
+            domain.validate(false),
+            Err(TemplateError::Validation(e)) if e == "vcpus (2) can't be greater than maxvcpus (1)"
+        ));
+    }
+
+    #[test]
+    fn test_domain_diff() {
+        let domain = Domain {
+            name: DomainName("xenith".to_string()),
+            memory: MemoryCapacity(2048),
+            network_interfaces: NetworkInterfaces(vec![NetworkInterface::default()]),
+            ..Default::default()
+        };
+        assert!(domain.diff(&domain.clone()).is_empty());
+
+        let mut edited = domain.clone();
+        edited.memory = MemoryCapacity(4096);
+        edited.network_interfaces.push(NetworkInterface {
+            mac: NetworkInterface::deterministic_mac("xenith", 1),
+            ..Default::default()
+        });
+        // Not part of the xl configuration
+        edited.disks.push(Disk::default());
+        edited.disks.0[0].size = 1024;
+        let mut resized = edited.clone();
+        resized.disks.0[0].size = 2048;
+        assert!(edited.diff(&resized).is_empty());
+
+        let changes = domain.diff(&edited);
+        let fields = changes
+            .iter()
+            .map(|change| change.field)
+            .collect::<Vec<&str>>();
+        assert_eq!(fields, ["memory", "disks", "network_interfaces"]);
+        assert_eq!(
+            changes[0],
+            FieldChange {
+                field: "memory",
+                old: "memory = 2048".to_string(),
+                new: "memory = 4096".to_string(),
+            }
+        );
+        assert_eq!(
+            changes[0].to_string(),
+            "memory: memory = 2048 -> memory = 4096"
+        );
+        assert_eq!(
+            changes[2].new.matches("mac=").count(),
+            2,
+            "{}",
+            changes[2].new
+        );
+
+        edited.set_label("owner", "ops");
+        let changes = domain.diff(&edited);
+        assert_eq!(
+            changes.last().unwrap().to_string(),
+            "labels: (none) -> owner=ops"
+        );
+    }
+
+    #[test]
+    fn test_domain_preset() {
+        for os in [GuestOs::Debian12, GuestOs::Ubuntu2404] {
+            let domain = Domain::preset(os);
+            assert_eq!(domain.firmware, Firmware::Uefi);
+            assert_eq!(
+                domain.emulated_disk_controller,
+                EmulatedDiskControllerType::Ahci
+            );
+            assert_eq!(
+                domain.network_interfaces.0[0].r#type,
+                NetworkInterfaceType::Vif
+            );
+            assert_eq!(domain.network_interfaces.0[0].model, None);
+            assert_eq!(domain.viridian, Viridian(false));
+            assert_eq!(domain.clock_offset, ClockOffset::Utc);
+        }
+
+        for os in [GuestOs::Windows10, GuestOs::Windows11] {
+            let domain = Domain::preset(os);
+            assert_eq!(domain.firmware, Firmware::Uefi);
+            assert_eq!(
+                domain.network_interfaces.0[0].model,
+                Some(NetworkInterfaceModel::E1000)
+            );
+            assert_eq!(domain.viridian, Viridian(true));
+            assert_eq!(domain.clock_offset, ClockOffset::LocalTime);
+            assert!(domain.xl_config().contains("viridian = 1\n"));
+            assert!(domain.xl_config().contains("localtime = 1\n"));
+        }
+    }
+
     #[test]
     fn test_domain_name_display() {
         assert_eq!(
@@ -381,20 +1401,129 @@ mod tests {
         assert_eq!(domain.name, DomainName::default());
         assert_eq!(domain.virtual_cpus, VirtualCpuNumber(0));
         assert_eq!(domain.maximum_virtual_cpus, MaximumVirtualCpuNumber(0));
+        assert_eq!(domain.cpu_topology, None);
+        assert_eq!(domain.cpu_model, None);
         assert_eq!(domain.memory, MemoryCapacity(0));
         assert_eq!(domain.maximum_memory, MaximumMemoryCapacity(0));
+        assert_eq!(domain.memory_backing, MemoryBacking::Default);
         assert_eq!(domain.disks, DiskDevices::default());
         assert_eq!(domain.network_interfaces, NetworkInterfaces::default());
         assert_eq!(domain.domain_actions, DomainActions::default());
+        assert_eq!(domain.watchdog, None);
         assert_eq!(domain.firmware, Firmware::default());
         assert_eq!(domain.boot_devices, BootDevices::default());
         assert_eq!(
             domain.emulated_disk_controller,
             EmulatedDiskControllerType::default()
         );
+        assert_eq!(domain.device_model_version, DeviceModelVersion::QemuXen);
+        assert_eq!(domain.sound_hardware, SoundHardware::None);
+        assert_eq!(domain.video_adapter, None);
+        assert_eq!(domain.console, None);
+        assert_eq!(domain.usb_tablet, None);
+        assert!(!domain.usb_tablet_enabled());
         assert_eq!(domain.alternate_p2m, AlternateP2mMode::default());
         assert_eq!(domain.nested_hvm, NestedHvm::default());
         assert_eq!(domain.smbios, SmBios::default());
         assert_eq!(domain.tsc_mode, TimeStampCounterMode::default());
+        assert!(domain.extra_config.is_empty());
+    }
+
+    #[test]
+    fn test_domain_usb_tablet() {
+        let mut domain = Domain {
+            console: Some(GuestConsole::Vnc),
+            ..Default::default()
+        };
+        assert!(domain.usb_tablet_enabled());
+        assert_eq!(domain.usb_devices_xl_config(), "usbdevice = \"tablet\"");
+
+        domain.usb_tablet = Some(false);
+        assert!(!domain.usb_tablet_enabled());
+        assert_eq!(domain.usb_devices_xl_config(), "");
+
+        domain.usb_tablet = None;
+        domain.r#type = DomainType::Pvh;
+        assert!(!domain.usb_tablet_enabled());
+
+        domain.usb_tablet = Some(true);
+        assert_eq!(domain.usb_devices_xl_config(), "");
+    }
+
+    #[test]
+    fn test_domain_watchdog() {
+        let mut domain = Domain {
+            watchdog: Some(Watchdog {
+                model: WatchdogModel::Xen,
+                action: EventAction::Restart,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            domain.effective_domain_actions().on_watchdog,
+            EventAction::Restart
+        );
+        assert_eq!(domain.watchdog_xl_config(), "");
+
+        domain.watchdog = Some(Watchdog {
+            model: WatchdogModel::I6300esb,
+            action: EventAction::Restart,
+        });
+        assert_eq!(
+            domain.effective_domain_actions().on_watchdog,
+            EventAction::Destroy
+        );
+        assert_eq!(
+            domain.watchdog_xl_config(),
+            "device_model_args_hvm = [ \"-device\", \"i6300esb\", \"-action\", \"watchdog=reset\" ]"
+        );
+    }
+
+    #[test]
+    fn test_nested_hvm_serde_round_trip() {
+        for nested_hvm in [NestedHvm(false), NestedHvm(true)] {
+            let serialized = serde_json::to_string(&nested_hvm).unwrap();
+            assert_eq!(
+                serde_json::from_str::<NestedHvm>(&serialized).unwrap(),
+                nested_hvm
+            );
+        }
+
+        // Newtypes are serialized as their inner value
+        assert_eq!(serde_json::to_string(&NestedHvm(true)).unwrap(), "true");
+    }
+
+    #[test]
+    fn test_domain_labels() {
+        let mut domain = Domain::default();
+        assert_eq!(domain.label("owner"), None);
+
+        assert_eq!(domain.set_label("owner", "alice"), None);
+        assert_eq!(domain.set_label("environment", "lab"), None);
+        assert_eq!(domain.set_label("owner", "bob"), Some("alice".to_string()));
+        assert_eq!(domain.label("owner"), Some("bob"));
+        assert_eq!(domain.label("environment"), Some("lab"));
+
+        // Labels are not part of the xl configuration
+        assert!(!domain.xl_config().contains("bob"));
+    }
+
+    #[test]
+    fn test_domain_labels_serde() {
+        let mut domain = Domain::default();
+        domain.set_label("project", "xenith");
+        domain.set_label("environment", "lab");
+        domain.set_label("owner", "bob");
+
+        let serialized = serde_json::to_value(&domain).unwrap();
+        // Labels are sorted by key, whatever the insertion order
+        assert_eq!(
+            serialized["labels"].to_string(),
+            r#"{"environment":"lab","owner":"bob","project":"xenith"}"#
+        );
+
+        let deserialized: Domain = serde_json::from_value(serialized).unwrap();
+        assert_eq!(deserialized.label("project"), Some("xenith"));
+        assert_eq!(deserialized, domain);
     }
 }
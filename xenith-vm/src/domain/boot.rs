@@ -19,6 +19,9 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 //! Boot configuration structures and options for a domain.
 
 use crate::XlConfiguration;
+use crate::error::TemplateError;
+
+use serde::{Deserialize, Serialize};
 
 use std::fmt::Display;
 use std::path::PathBuf;
@@ -32,7 +35,7 @@ use std::path::PathBuf;
 /// domain.
 ///
 /// ⚠️ Those options are only available for HVM guests.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum Firmware {
     /// Boot the guest using the default BIOS firmware, which depends on the chosen
     /// device model.
@@ -69,6 +72,62 @@ impl XlConfiguration for Firmware {
     }
 }
 
+/// Direct kernel boot, where the toolstack loads the guest kernel from the host file system
+/// instead of booting a firmware. This is required for PV guests without a bootloader.
+///
+/// ⚠️ Only available with the `pv-support` feature, as PV and PVH guests are not supported
+/// by Xenith yet.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct DirectKernelBoot {
+    /// Path of the kernel image, on the host
+    pub kernel: PathBuf,
+    /// Path of the initial ramdisk, on the host
+    pub ramdisk: Option<PathBuf>,
+    /// Command line passed to the kernel
+    pub command_line: Option<String>,
+}
+
+impl DirectKernelBoot {
+    /// Validate the direct kernel boot options
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if the kernel path is empty or if the command
+    /// line contains a double quote, which can't be escaped in `xl.cfg`
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        if self.kernel.as_os_str().is_empty() {
+            return Err(TemplateError::Validation(
+                "direct kernel boot requires a kernel".to_string(),
+            ));
+        }
+        if self
+            .command_line
+            .as_ref()
+            .is_some_and(|command_line| command_line.contains('"'))
+        {
+            return Err(TemplateError::Validation(
+                "kernel command line must not contain '\"'".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl XlConfiguration for DirectKernelBoot {
+    // kernel="PATHNAME", ramdisk="PATHNAME", cmdline="STRING"
+    fn xl_config(&self) -> String {
+        let mut options = vec![format!("kernel = \"{}\"", self.kernel.display())];
+        if let Some(ramdisk) = &self.ramdisk {
+            options.push(format!("ramdisk = \"{}\"", ramdisk.display()));
+        }
+        if let Some(command_line) = &self.command_line {
+            options.push(format!("cmdline = \"{}\"", command_line));
+        }
+        options.join("\n")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,4 +157,50 @@ mod tests {
             "firmware = \"/path/to/file\""
         );
     }
+
+    #[test]
+    fn test_direct_kernel_boot_xl_config() {
+        let mut direct_kernel_boot = DirectKernelBoot {
+            kernel: PathBuf::from("/boot/vmlinuz"),
+            ..Default::default()
+        };
+        assert!(direct_kernel_boot.validate().is_ok());
+        assert_eq!(direct_kernel_boot.xl_config(), "kernel = \"/boot/vmlinuz\"");
+
+        direct_kernel_boot.ramdisk = Some(PathBuf::from("/boot/initrd.img"));
+        direct_kernel_boot.command_line = Some("root=/dev/xvda1 ro".to_string());
+        assert_eq!(
+            direct_kernel_boot.xl_config(),
+            "kernel = \"/boot/vmlinuz\"\nramdisk = \"/boot/initrd.img\"\ncmdline = \"root=/dev/xvda1 ro\""
+        );
+
+        direct_kernel_boot.command_line = Some("console=\"hvc0\"".to_string());
+        assert!(direct_kernel_boot.validate().is_err());
+        assert!(DirectKernelBoot::default().validate().is_err());
+    }
+
+    #[test]
+    fn test_firmware_serde_round_trip() {
+        for firmware in [
+            Firmware::Bios,
+            Firmware::Uefi,
+            Firmware::Seabios,
+            Firmware::Rombios,
+            Firmware::Ovmf,
+            Firmware::Path(PathBuf::from("/usr/lib/xen/boot/ovmf.bin")),
+        ] {
+            let serialized = serde_json::to_string(&firmware).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Firmware>(&serialized).unwrap(),
+                firmware
+            );
+        }
+
+        // The path is serialized as a plain string
+        assert_eq!(
+            serde_json::to_string(&Firmware::Path(PathBuf::from("/usr/lib/xen/boot/ovmf.bin")))
+                .unwrap(),
+            r#"{"Path":"/usr/lib/xen/boot/ovmf.bin"}"#
+        );
+    }
 }
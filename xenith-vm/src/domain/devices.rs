@@ -18,13 +18,18 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 //! Domain disk configuration structures and options for a domain.
 
+use serde::{Deserialize, Serialize};
+
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 use crate::XlConfiguration;
+use crate::domain::DomainName;
+use crate::error::{DiskError, TemplateError};
 
 /// List of supported disk formats
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum DiskFormat {
     /// This is a simple, unstructured format that provides direct access to the disk image.
     /// It is straightforward and offers good performance but lacks advanced features like snapshots.
@@ -47,6 +52,22 @@ pub enum DiskFormat {
     Qed,
 }
 
+impl DiskFormat {
+    /// Check if the format supports internal snapshots, stored in the image itself
+    ///
+    /// QED dropped the internal snapshots of qcow2 for performance, raw and VHD images have
+    /// no metadata to store them.
+    pub fn supports_snapshots(&self) -> bool {
+        matches!(self, DiskFormat::Qcow | DiskFormat::Qcow2)
+    }
+
+    /// Check if the format supports backing files, i.e. can be used in an overlay chain
+    /// where the image only stores the clusters written since its backing file
+    pub fn supports_backing(&self) -> bool {
+        matches!(self, DiskFormat::Qcow | DiskFormat::Qcow2 | DiskFormat::Qed)
+    }
+}
+
 impl Display for DiskFormat {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -60,7 +81,7 @@ impl Display for DiskFormat {
 }
 
 /// Access control information for a disk
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum DiskAccess {
     ReadOnly,
     #[default]
@@ -83,8 +104,12 @@ impl Display for DiskAccess {
 /// It can be accessed in read-only or read-write mode and attached to a specific
 /// device like `xvda` or `sda`.
 ///
+/// The serial number and WWN reported by the emulated disks can't be set: the `xl` disk
+/// spec has no parameter for them, and libxl builds the device model arguments of the
+/// disks itself.
+///
 /// See `man xl-disk-configuration` for more information.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct Disk {
     /// Block device or image file path.  When this is used as a path, /dev will be
     /// prepended if the path doesn't start with a '/'.
@@ -100,6 +125,158 @@ pub struct Disk {
     /// Virtual device as seen by the guest (also referred to as guest drive
     /// designation in some specifications).  See xen-vbd-interface(7).
     pub virtual_device: String,
+    /// Emulated disk controller the disk is attached to. When `None`, the disk is
+    /// attached to the domain's default controller.
+    ///
+    /// ⚠️ Only available for HVM guests. `xl` attaches every disk to the single `hdtype`
+    /// controller of the domain, so this must match it, see
+    /// [`DiskDevices::validate_controller`].
+    pub controller: Option<EmulatedDiskControllerType>,
+    /// Name of the driver domain running the disk backend. When `None`, the backend
+    /// runs in dom0.
+    pub backend_domain: Option<String>,
+}
+
+impl Disk {
+    /// Get the disk number and partition of the virtual device, as defined by
+    /// xen-vbd-interface(7).
+    ///
+    /// `xvda`, `hda` and `sda` all refer to the first disk of the guest, which
+    /// means they can't be used together.
+    ///
+    /// # Returns
+    ///
+    /// The disk number and the partition number (0 for the whole disk), or `None`
+    /// if the virtual device doesn't follow the `xvd`/`hd`/`sd` naming scheme
+    pub fn virtual_device_number(&self) -> Option<(u32, u32)> {
+        let name = ["xvd", "hd", "sd"]
+            .iter()
+            .find_map(|prefix| self.virtual_device.strip_prefix(prefix))?;
+
+        let letters_end = name
+            .find(|c: char| !c.is_ascii_lowercase())
+            .unwrap_or(name.len());
+        let (letters, partition) = name.split_at(letters_end);
+        if letters.is_empty() {
+            return None;
+        }
+
+        // Disk letters are a bijective base-26 number (a = 0, z = 25, aa = 26, ...)
+        let mut disk = 0u32;
+        for c in letters.chars() {
+            disk = disk
+                .checked_mul(26)?
+                .checked_add(c as u32 - 'a' as u32 + 1)?;
+        }
+
+        let partition = match partition {
+            "" => 0,
+            partition => partition.parse().ok()?,
+        };
+
+        Some((disk - 1, partition))
+    }
+
+    /// Validate the disk
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if the backend domain name is invalid
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        if let Some(backend_domain) = &self.backend_domain {
+            DomainName(backend_domain.clone()).validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the target of the disk, as used by `xl`
+    ///
+    /// `/dev` is prepended to relative targets, e.g. `sdb` becomes `/dev/sdb`. Absolute
+    /// and empty (e.g. ejected CD-ROM) targets are left unchanged.
+    ///
+    /// # Returns
+    ///
+    /// The normalized target path
+    pub fn normalized_target(&self) -> PathBuf {
+        if self.target.as_os_str().is_empty() || self.target.is_absolute() {
+            self.target.clone()
+        } else {
+            Path::new("/dev").join(&self.target)
+        }
+    }
+
+    /// Check if the disk target is a block device rather than an image file
+    ///
+    /// The check is done on the normalized target path, which must be under `/dev`, so it
+    /// doesn't depend on the devices of the host generating the configuration.
+    pub fn is_block_device(&self) -> bool {
+        let target = self.normalized_target();
+        target != Path::new("/dev") && target.starts_with("/dev")
+    }
+
+    /// Create an external snapshot of the disk
+    ///
+    /// The snapshot is a qcow2 overlay backed by the current disk image, created with
+    /// `qemu-img`. The current image must not be written to anymore once the snapshot is
+    /// taken, as the guest writes go to the overlay.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - The directory to create the snapshot in, e.g. the domain `snapshots/` directory
+    /// * `snapshot_name` - The name of the snapshot, used as file name
+    ///
+    /// # Returns
+    ///
+    /// The snapshot [`Disk`], to be attached to the domain in place of the current one
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DiskError`] if the disk format doesn't support backing files (see
+    /// [`DiskFormat::supports_backing`]), if the snapshot already exists or if `qemu-img` fails
+    pub fn create_snapshot(
+        &self,
+        directory: &Path,
+        snapshot_name: &str,
+    ) -> Result<Disk, DiskError> {
+        // The current image becomes the base of a backing chain
+        if !self.format.supports_backing() {
+            return Err(DiskError::SnapshotUnsupported(self.format.clone()));
+        }
+
+        let target = directory.join(format!("{}.qcow2", snapshot_name));
+        if target.exists() {
+            return Err(DiskError::SnapshotAlreadyExists(target));
+        }
+        std::fs::create_dir_all(directory)?;
+
+        // The backing file path is stored in the overlay, it must not depend on the working directory
+        let backing_file = std::path::absolute(self.normalized_target())?;
+        let output = Command::new("qemu-img")
+            .args([
+                "create",
+                "-f",
+                "qcow2",
+                "-F",
+                &self.format.to_string(),
+                "-b",
+            ])
+            .arg(&backing_file)
+            .arg(&target)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(DiskError::QemuImg(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+
+        Ok(Disk {
+            target,
+            format: DiskFormat::Qcow2,
+            ..self.clone()
+        })
+    }
 }
 
 impl Display for Disk {
@@ -109,37 +286,252 @@ impl Display for Disk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "format={}, vdev={}, access={}, target={}",
-            self.format,
-            self.virtual_device,
-            self.access,
-            self.target.display()
-        )
+            "format={}, vdev={}, access={}, ",
+            self.format, self.virtual_device, self.access
+        )?;
+        if let Some(backend_domain) = &self.backend_domain {
+            write!(f, "backend={}, ", backend_domain)?;
+        }
+        // The target must be the last parameter, as it may contain commas
+        write!(f, "target={}", self.normalized_target().display())
+    }
+}
+
+/// Represents a conflict between two disks of a domain, see [`DiskDevices::conflicts`]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum DiskConflict {
+    /// Both disks, given by their virtual device, are the same disk of the guest
+    VirtualDevice(String, String),
+    /// Both disks, given by their virtual device, use the same target
+    Target(String, String, PathBuf),
+}
+
+impl Display for DiskConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiskConflict::VirtualDevice(first, second) => {
+                write!(
+                    f,
+                    "disks {} and {} use the same virtual device",
+                    first, second
+                )
+            }
+            DiskConflict::Target(first, second, target) => write!(
+                f,
+                "disks {} and {} use the same target {}",
+                first,
+                second,
+                target.display()
+            ),
+        }
     }
 }
 
 /// Represents a list of disk devices attached to a virtual machine
 /// The disk devices can be used for storing the operating system, data, or other files.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct DiskDevices(pub Vec<Disk>);
 
+impl DiskDevices {
+    /// Attach a disk
+    pub fn push(&mut self, disk: Disk) {
+        self.0.push(disk);
+    }
+
+    /// Iterate over the disks
+    pub fn iter(&self) -> std::slice::Iter<'_, Disk> {
+        self.0.iter()
+    }
+
+    /// Get the number of disks
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if there is no disk
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromIterator<Disk> for DiskDevices {
+    fn from_iter<I: IntoIterator<Item = Disk>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for DiskDevices {
+    type Item = Disk;
+    type IntoIter = std::vec::IntoIter<Disk>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a DiskDevices {
+    type Item = &'a Disk;
+    type IntoIter = std::slice::Iter<'a, Disk>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl DiskDevices {
+    /// Assign a virtual device to the disks without one
+    ///
+    /// Disks with an empty virtual device get the first unused `xvd` name, in order
+    /// (`xvda`, `xvdb`, ..., `xvdz`, `xvdaa`, ...). Disks already using a name, whatever its
+    /// prefix, keep it and their disk number is skipped.
+    pub fn assign_default_vdevs(&mut self) {
+        let mut used = self
+            .0
+            .iter()
+            .filter_map(|disk| disk.virtual_device_number().map(|(number, _)| number))
+            .collect::<Vec<u32>>();
+
+        let mut next = 0;
+        for disk in self
+            .0
+            .iter_mut()
+            .filter(|disk| disk.virtual_device.is_empty())
+        {
+            while used.contains(&next) {
+                next += 1;
+            }
+            disk.virtual_device = format!("xvd{}", Self::disk_letters(next));
+            used.push(next);
+        }
+    }
+
+    /// Convert a disk number to its letters, as a bijective base-26 number
+    /// (0 = a, 25 = z, 26 = aa, ...)
+    fn disk_letters(number: u32) -> String {
+        let mut letters = Vec::new();
+        let mut number = number + 1;
+        while number > 0 {
+            number -= 1;
+            letters.push((b'a' + (number % 26) as u8) as char);
+            number /= 26;
+        }
+        letters.iter().rev().collect()
+    }
+
+    /// Get the conflicts between the disks
+    ///
+    /// Two disks conflict when they use the same virtual device, e.g. `xvda` and `sda` which
+    /// are both the first disk of the guest, or the same target. Disks without a target, e.g.
+    /// ejected CD-ROMs, never conflict on their target.
+    ///
+    /// # Returns
+    ///
+    /// The conflicts, in disk order
+    pub fn conflicts(&self) -> Vec<DiskConflict> {
+        let mut conflicts = Vec::new();
+        for (i, disk) in self.0.iter().enumerate() {
+            for other in &self.0[..i] {
+                let same_virtual_device =
+                    match (disk.virtual_device_number(), other.virtual_device_number()) {
+                        (Some(number), Some(other_number)) => number == other_number,
+                        _ => disk.virtual_device == other.virtual_device,
+                    };
+                if same_virtual_device {
+                    conflicts.push(DiskConflict::VirtualDevice(
+                        other.virtual_device.clone(),
+                        disk.virtual_device.clone(),
+                    ));
+                }
+
+                let target = disk.normalized_target();
+                if !target.as_os_str().is_empty() && target == other.normalized_target() {
+                    conflicts.push(DiskConflict::Target(
+                        other.virtual_device.clone(),
+                        disk.virtual_device.clone(),
+                        target,
+                    ));
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Validate the disk devices
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if a disk is invalid or listing all the
+    /// conflicts between the disks, see [`DiskDevices::conflicts`]
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        for disk in &self.0 {
+            disk.validate()?;
+        }
+
+        let conflicts = self.conflicts();
+        if !conflicts.is_empty() {
+            let conflicts = conflicts
+                .iter()
+                .map(DiskConflict::to_string)
+                .collect::<Vec<String>>();
+            return Err(TemplateError::Validation(conflicts.join(", ")));
+        }
+
+        Ok(())
+    }
+
+    /// Check that the disks are attached to the domain's controller
+    ///
+    /// `xl` only supports a single `hdtype` per domain and attaches every disk to it, so
+    /// a disk can't be explicitly attached to another controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `default` - The domain's emulated disk controller
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if a disk is attached to another controller
+    pub fn validate_controller(
+        &self,
+        default: &EmulatedDiskControllerType,
+    ) -> Result<(), TemplateError> {
+        for disk in &self.0 {
+            if let Some(controller) = &disk.controller {
+                if controller != default {
+                    return Err(TemplateError::Validation(format!(
+                        "disk {} can't be attached to the {} controller, xl attaches every disk to the {} controller of the domain",
+                        disk.virtual_device, controller, default
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl XlConfiguration for DiskDevices {
     // disk=[ "DISK_SPEC_STRING", "DISK_SPEC_STRING", ...]
+    // the target is always written last in the spec, as it is the only
+    // positional parameter allowed to contain commas
     fn xl_config(&self) -> String {
-        let mut disks = String::new();
-        for disk in &self.0 {
-            disks.push_str(&format!("\"{}\", ", disk));
+        if self.0.is_empty() {
+            return "disk = [ ]".to_string();
         }
-        disks.pop();
-        disks.pop();
-        format!("disk = [ {} ]", disks)
+
+        let disks = self
+            .0
+            .iter()
+            .map(|disk| format!("\"{}\"", disk))
+            .collect::<Vec<String>>();
+        format!("disk = [ {} ]", disks.join(", "))
     }
 }
 
 /// Represents the boot device for the virtual machine
 ///
 /// The boot device is used to specify the device from which the virtual machine should boot.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum BootDevice {
     #[default]
     HardDisk,
@@ -158,9 +550,64 @@ impl Display for BootDevice {
 }
 
 /// Represents the list of boot devices for the virtual machine
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct BootDevices(pub Vec<BootDevice>);
 
+impl BootDevices {
+    /// Parse a boot order in the `xl` format, e.g. `dc` to boot from the CD-ROM then the
+    /// hard disk
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The boot order, `c` (hard disk), `d` (CD-ROM) and `n` (network) characters
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if the order contains an unknown boot device
+    pub fn with_order(order: &str) -> Result<Self, TemplateError> {
+        order
+            .chars()
+            .map(|c| match c {
+                'c' => Ok(BootDevice::HardDisk),
+                'd' => Ok(BootDevice::CdRom),
+                'n' => Ok(BootDevice::Network),
+                c => Err(TemplateError::Validation(format!(
+                    "unknown boot device '{}' in boot order \"{}\"",
+                    c, order
+                ))),
+            })
+            .collect()
+    }
+
+    /// Append a boot device, tried after the current ones
+    pub fn push(&mut self, boot_device: BootDevice) {
+        self.0.push(boot_device);
+    }
+
+    /// Iterate over the boot devices, in boot order
+    pub fn iter(&self) -> std::slice::Iter<'_, BootDevice> {
+        self.0.iter()
+    }
+
+    /// Remove the repeated boot devices, keeping the first occurrence of each
+    pub fn dedup(&mut self) {
+        let mut seen = Vec::new();
+        self.0.retain(|boot_device| {
+            let first = !seen.contains(boot_device);
+            if first {
+                seen.push(boot_device.clone());
+            }
+            first
+        });
+    }
+}
+
+impl FromIterator<BootDevice> for BootDevices {
+    fn from_iter<I: IntoIterator<Item = BootDevice>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
 impl XlConfiguration for BootDevices {
     // boot="BOOT_DEVICE_STRING"
     fn xl_config(&self) -> String {
@@ -173,7 +620,7 @@ impl XlConfiguration for BootDevices {
 }
 
 /// Represents the type of emulated disk controller to use
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum EmulatedDiskControllerType {
     /// Adds an emulated IDE controller, which is
     /// suitable even for older operation systems.
@@ -202,6 +649,180 @@ impl XlConfiguration for EmulatedDiskControllerType {
     }
 }
 
+/// Represents the device model emulating the hardware of the virtual machine
+///
+/// The device model determines which emulated devices are available, e.g. AHCI controllers and
+/// UEFI firmwares require upstream QEMU.
+///
+/// ⚠️ Only available for HVM guests.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum DeviceModelVersion {
+    /// Upstream QEMU, with the Xen specific patches merged
+    #[default]
+    QemuXen,
+    /// The historical Xen fork of QEMU, only suitable for legacy guests
+    QemuXenTraditional,
+}
+
+impl Display for DeviceModelVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceModelVersion::QemuXen => write!(f, "qemu-xen"),
+            DeviceModelVersion::QemuXenTraditional => write!(f, "qemu-xen-traditional"),
+        }
+    }
+}
+
+impl XlConfiguration for DeviceModelVersion {
+    // device_model_version="DEVICE-MODEL"
+    fn xl_config(&self) -> String {
+        format!("device_model_version = \"{}\"", self)
+    }
+}
+
+/// Represents the emulated sound hardware of the virtual machine
+///
+/// ⚠️ Only available for HVM guests.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum SoundHardware {
+    /// No sound hardware is emulated
+    #[default]
+    None,
+    /// Intel High Definition Audio, supported out of the box by modern guests
+    Hda,
+    /// Intel AC'97 audio, suitable for older guests
+    Ac97,
+    /// Creative Sound Blaster 16
+    Sb16,
+}
+
+impl Display for SoundHardware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SoundHardware::None => write!(f, "none"),
+            SoundHardware::Hda => write!(f, "hda"),
+            SoundHardware::Ac97 => write!(f, "ac97"),
+            SoundHardware::Sb16 => write!(f, "sb16"),
+        }
+    }
+}
+
+impl XlConfiguration for SoundHardware {
+    // soundhw="DEVICE"
+    fn xl_config(&self) -> String {
+        match self {
+            SoundHardware::None => String::new(),
+            _ => format!("soundhw = \"{}\"", self),
+        }
+    }
+}
+
+/// Represents the model of emulated graphics card
+///
+/// ⚠️ Only available for HVM guests.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum VgaModel {
+    /// Standard VGA card with Bochs VBE extensions, supporting high resolutions.
+    #[default]
+    Stdvga,
+    /// Cirrus Logic GD5446, supported by very old guests.
+    Cirrus,
+    /// QXL paravirtual graphics card, best used with a SPICE console.
+    Qxl,
+    /// No emulated graphics card.
+    None,
+}
+
+impl VgaModel {
+    /// Minimum amount of video memory in MB accepted by the upstream device model
+    pub fn minimum_memory_mb(&self) -> u16 {
+        match self {
+            VgaModel::Stdvga => 16,
+            VgaModel::Cirrus => 8,
+            VgaModel::Qxl => 128,
+            VgaModel::None => 0,
+        }
+    }
+}
+
+impl Display for VgaModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VgaModel::Stdvga => write!(f, "stdvga"),
+            VgaModel::Cirrus => write!(f, "cirrus"),
+            VgaModel::Qxl => write!(f, "qxl"),
+            VgaModel::None => write!(f, "none"),
+        }
+    }
+}
+
+/// Represents the emulated graphics card of the virtual machine
+///
+/// ⚠️ Only available for HVM guests.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct VideoAdapter {
+    /// Model of the graphics card
+    pub model: VgaModel,
+    /// Amount of video memory in MB. The device model default is used when `None`.
+    pub memory_mb: Option<u16>,
+}
+
+impl VideoAdapter {
+    /// Maximum amount of video memory in MB, the size of the device model VGA memory BAR
+    pub const MAXIMUM_MEMORY_MB: u16 = 256;
+
+    /// Validate the video adapter configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if the video memory is out of the bounds
+    /// accepted by the graphics card model
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        let Some(memory_mb) = self.memory_mb else {
+            return Ok(());
+        };
+
+        if self.model == VgaModel::None {
+            return Err(TemplateError::Validation(
+                "video memory can't be set without a graphics card".to_string(),
+            ));
+        }
+
+        let minimum_memory_mb = self.model.minimum_memory_mb();
+        if !(minimum_memory_mb..=VideoAdapter::MAXIMUM_MEMORY_MB).contains(&memory_mb) {
+            return Err(TemplateError::Validation(format!(
+                "video memory of {} must be between {} and {} MB",
+                self.model,
+                minimum_memory_mb,
+                VideoAdapter::MAXIMUM_MEMORY_MB
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for VideoAdapter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.memory_mb {
+            Some(memory_mb) => write!(f, "{} ({} MB)", self.model, memory_mb),
+            None => write!(f, "{}", self.model),
+        }
+    }
+}
+
+impl XlConfiguration for VideoAdapter {
+    // vga="MODEL"
+    // videoram=MBYTES
+    fn xl_config(&self) -> String {
+        let vga = format!("vga = \"{}\"", self.model);
+        match self.memory_mb {
+            Some(memory_mb) => format!("{}\nvideoram = {}", vga, memory_mb),
+            None => vga,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -215,6 +836,52 @@ mod tests {
         assert_eq!(format!("{}", DiskFormat::Qed), "qed");
     }
 
+    #[test]
+    fn test_disk_format_capabilities() {
+        for (format, snapshots, backing) in [
+            (DiskFormat::Raw, false, false),
+            (DiskFormat::Qcow, true, true),
+            (DiskFormat::Qcow2, true, true),
+            (DiskFormat::Vhd, false, false),
+            (DiskFormat::Qed, false, true),
+        ] {
+            assert_eq!(format.supports_snapshots(), snapshots, "{}", format);
+            assert_eq!(format.supports_backing(), backing, "{}", format);
+        }
+    }
+
+    #[test]
+    fn test_disk_devices_collection() {
+        let mut disk_devices = ["xvda", "xvdb"]
+            .into_iter()
+            .map(|virtual_device| Disk {
+                virtual_device: virtual_device.to_string(),
+                ..Default::default()
+            })
+            .collect::<DiskDevices>();
+        assert_eq!(disk_devices.len(), 2);
+        assert!(!disk_devices.is_empty());
+        assert!(DiskDevices::default().is_empty());
+
+        disk_devices.push(Disk {
+            virtual_device: "xvdc".to_string(),
+            ..Default::default()
+        });
+        let virtual_devices = disk_devices
+            .iter()
+            .map(|disk| disk.virtual_device.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(virtual_devices, ["xvda", "xvdb", "xvdc"]);
+
+        let mut count = 0;
+        for disk in &disk_devices {
+            assert!(disk.virtual_device.starts_with("xvd"));
+            count += 1;
+        }
+        assert_eq!(count, 3);
+        assert_eq!(disk_devices.into_iter().count(), 3);
+    }
+
     #[test]
     fn test_disk_access_display() {
         assert_eq!(format!("{}", DiskAccess::ReadOnly), "ro");
@@ -229,6 +896,8 @@ mod tests {
             format: DiskFormat::Qcow2,
             access: DiskAccess::ReadWrite,
             virtual_device: "xvda".to_string(),
+            controller: None,
+            backend_domain: None,
         };
         assert_eq!(
             format!("{}", disk),
@@ -236,6 +905,83 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_disk_normalized_target() {
+        let disk = |target: &str| Disk {
+            target: PathBuf::from(target),
+            ..Default::default()
+        };
+
+        // Relative path
+        assert_eq!(disk("sdb").normalized_target(), PathBuf::from("/dev/sdb"));
+        assert!(disk("sdb").is_block_device());
+        assert_eq!(
+            disk("sdb").to_string(),
+            "format=qcow2, vdev=, access=rw, target=/dev/sdb"
+        );
+
+        // Absolute image path
+        assert_eq!(
+            disk("/var/lib/xen/images/disk.qcow2").normalized_target(),
+            PathBuf::from("/var/lib/xen/images/disk.qcow2")
+        );
+        assert!(!disk("/var/lib/xen/images/disk.qcow2").is_block_device());
+
+        // Block device
+        assert_eq!(
+            disk("/dev/sda").normalized_target(),
+            PathBuf::from("/dev/sda")
+        );
+        assert!(disk("/dev/sda").is_block_device());
+
+        assert_eq!(disk("").normalized_target(), PathBuf::new());
+        assert!(!disk("").is_block_device());
+    }
+
+    #[test]
+    fn test_disk_backend_domain() {
+        let mut disk = Disk {
+            target: PathBuf::from("/dev/sda"),
+            virtual_device: "xvda".to_string(),
+            backend_domain: Some("storage-driver".to_string()),
+            ..Default::default()
+        };
+        assert!(disk.validate().is_ok());
+        assert_eq!(
+            disk.to_string(),
+            "format=qcow2, vdev=xvda, access=rw, backend=storage-driver, target=/dev/sda"
+        );
+
+        disk.backend_domain = Some(String::new());
+        assert!(matches!(
+            DiskDevices(vec![disk]).validate(),
+            Err(TemplateError::Validation(e)) if e == "invalid domain name \"\""
+        ));
+    }
+
+    #[test]
+    fn test_empty_disk_devices_xl_config() {
+        let disk_devices = DiskDevices::default();
+        assert_eq!(disk_devices.xl_config(), "disk = [ ]");
+    }
+
+    #[test]
+    fn test_single_disk_devices_xl_config() {
+        let disk_devices = DiskDevices(vec![Disk {
+            target: PathBuf::from("/var/lib/xenith/disk.qcow2"),
+            size: 1024,
+            format: DiskFormat::Qcow2,
+            access: DiskAccess::ReadWrite,
+            virtual_device: "xvda".to_string(),
+            controller: None,
+            backend_domain: None,
+        }]);
+        assert_eq!(
+            disk_devices.xl_config(),
+            "disk = [ \"format=qcow2, vdev=xvda, access=rw, target=/var/lib/xenith/disk.qcow2\" ]"
+        );
+    }
+
     #[test]
     fn test_disk_devices_xl_config() {
         let disk1 = Disk {
@@ -244,6 +990,8 @@ mod tests {
             format: DiskFormat::Qcow2,
             access: DiskAccess::ReadWrite,
             virtual_device: "xvda".to_string(),
+            controller: None,
+            backend_domain: None,
         };
         let disk2 = Disk {
             target: PathBuf::from("/dev/sdb"),
@@ -251,6 +999,8 @@ mod tests {
             format: DiskFormat::Raw,
             access: DiskAccess::ReadOnly,
             virtual_device: "xvdb".to_string(),
+            controller: None,
+            backend_domain: None,
         };
         let disk_devices = DiskDevices(vec![disk1, disk2]);
         assert_eq!(
@@ -276,6 +1026,59 @@ mod tests {
         assert_eq!(boot_devices.xl_config(), "boot = \"cdn\"");
     }
 
+    #[test]
+    fn test_boot_devices_with_order() {
+        assert_eq!(
+            BootDevices::with_order("ndc").unwrap(),
+            BootDevices(vec![
+                BootDevice::Network,
+                BootDevice::CdRom,
+                BootDevice::HardDisk,
+            ])
+        );
+        assert_eq!(
+            BootDevices::with_order("dc").unwrap().xl_config(),
+            "boot = \"dc\""
+        );
+        assert!(matches!(
+            BootDevices::with_order("x"),
+            Err(TemplateError::Validation(e)) if e == "unknown boot device 'x' in boot order \"x\""
+        ));
+    }
+
+    #[test]
+    fn test_boot_devices_collection() {
+        let mut boot_devices = [BootDevice::CdRom, BootDevice::HardDisk]
+            .into_iter()
+            .collect::<BootDevices>();
+        boot_devices.push(BootDevice::CdRom);
+        boot_devices.push(BootDevice::Network);
+        assert_eq!(boot_devices.xl_config(), "boot = \"dcdn\"");
+
+        boot_devices.dedup();
+        assert_eq!(
+            boot_devices.iter().cloned().collect::<Vec<BootDevice>>(),
+            [BootDevice::CdRom, BootDevice::HardDisk, BootDevice::Network]
+        );
+    }
+
+    #[test]
+    fn test_device_model_version_xl_config() {
+        assert_eq!(DeviceModelVersion::QemuXen.to_string(), "qemu-xen");
+        assert_eq!(
+            DeviceModelVersion::QemuXenTraditional.to_string(),
+            "qemu-xen-traditional"
+        );
+        assert_eq!(
+            DeviceModelVersion::QemuXen.xl_config(),
+            "device_model_version = \"qemu-xen\""
+        );
+        assert_eq!(
+            DeviceModelVersion::QemuXenTraditional.xl_config(),
+            "device_model_version = \"qemu-xen-traditional\""
+        );
+    }
+
     #[test]
     fn test_emulated_disk_controller_type_display() {
         assert_eq!(format!("{}", EmulatedDiskControllerType::Ide), "ide");
@@ -287,4 +1090,327 @@ mod tests {
         let disk_controller = EmulatedDiskControllerType::Ahci;
         assert_eq!(disk_controller.xl_config(), "hdtype = \"ahci\"");
     }
+
+    #[test]
+    fn test_disk_virtual_device_number() {
+        let disk = |virtual_device: &str| Disk {
+            virtual_device: virtual_device.to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(disk("xvda").virtual_device_number(), Some((0, 0)));
+        assert_eq!(disk("hda").virtual_device_number(), Some((0, 0)));
+        assert_eq!(disk("sdb3").virtual_device_number(), Some((1, 3)));
+        assert_eq!(disk("xvdaa").virtual_device_number(), Some((26, 0)));
+        assert_eq!(disk("vda").virtual_device_number(), None);
+        assert_eq!(disk("xvd").virtual_device_number(), None);
+    }
+
+    #[test]
+    fn test_disk_devices_assign_default_vdevs() {
+        let mut disk_devices = DiskDevices(vec![Disk::default(); 28]);
+        disk_devices.assign_default_vdevs();
+
+        let virtual_devices = disk_devices
+            .0
+            .iter()
+            .map(|disk| disk.virtual_device.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(virtual_devices[..3], ["xvda", "xvdb", "xvdc"]);
+        assert_eq!(virtual_devices[25..], ["xvdz", "xvdaa", "xvdab"]);
+        assert!(disk_devices.validate().is_ok());
+    }
+
+    #[test]
+    fn test_disk_devices_assign_default_vdevs_preset() {
+        let disk = |virtual_device: &str| Disk {
+            virtual_device: virtual_device.to_string(),
+            ..Default::default()
+        };
+        let mut disk_devices = DiskDevices(vec![disk(""), disk("hda"), disk(""), disk("xvdc")]);
+        disk_devices.assign_default_vdevs();
+
+        let virtual_devices = disk_devices
+            .0
+            .iter()
+            .map(|disk| disk.virtual_device.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(virtual_devices, ["xvdb", "hda", "xvdd", "xvdc"]);
+        assert!(disk_devices.validate().is_ok());
+    }
+
+    #[test]
+    fn test_disk_create_snapshot_raw() {
+        let mut disk = Disk {
+            target: PathBuf::from("/var/lib/xenith/disk.img"),
+            format: DiskFormat::Raw,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            disk.create_snapshot(&std::env::temp_dir(), "snapshot"),
+            Err(DiskError::SnapshotUnsupported(DiskFormat::Raw))
+        ));
+
+        disk.format = DiskFormat::Vhd;
+        assert!(matches!(
+            disk.create_snapshot(&std::env::temp_dir(), "snapshot"),
+            Err(DiskError::SnapshotUnsupported(DiskFormat::Vhd))
+        ));
+    }
+
+    #[test]
+    fn test_disk_create_snapshot() {
+        if Command::new("qemu-img").arg("--version").output().is_err() {
+            eprintln!("qemu-img is not installed, skipping");
+            return;
+        }
+
+        let directory =
+            std::env::temp_dir().join(format!("xenith-snapshot-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let target = directory.join("disk.qcow2");
+        let status = Command::new("qemu-img")
+            .args(["create", "-f", "qcow2"])
+            .arg(&target)
+            .arg("1M")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let disk = Disk {
+            target,
+            size: 1024 * 1024,
+            format: DiskFormat::Qcow2,
+            virtual_device: "xvda".to_string(),
+            ..Default::default()
+        };
+
+        let snapshot = disk
+            .create_snapshot(&directory.join("snapshots"), "before-update")
+            .unwrap();
+        assert_eq!(
+            snapshot.target,
+            directory.join("snapshots/before-update.qcow2")
+        );
+        assert!(snapshot.target.exists());
+        assert_eq!(snapshot.virtual_device, "xvda");
+
+        assert!(matches!(
+            disk.create_snapshot(&directory.join("snapshots"), "before-update"),
+            Err(DiskError::SnapshotAlreadyExists(_))
+        ));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_disk_devices_validate_controller() {
+        let mut disk_devices = DiskDevices(vec![
+            Disk {
+                target: PathBuf::from("/dev/nvme0n1"),
+                virtual_device: "hda".to_string(),
+                controller: Some(EmulatedDiskControllerType::Ahci),
+                ..Default::default()
+            },
+            Disk {
+                target: PathBuf::from("/dev/sdb"),
+                virtual_device: "hdb".to_string(),
+                ..Default::default()
+            },
+        ]);
+        assert!(
+            disk_devices
+                .validate_controller(&EmulatedDiskControllerType::Ahci)
+                .is_ok()
+        );
+
+        // Two-controller layout, which xl can't express
+        disk_devices.0[1].controller = Some(EmulatedDiskControllerType::Ide);
+        assert!(matches!(
+            disk_devices.validate_controller(&EmulatedDiskControllerType::Ahci),
+            Err(TemplateError::Validation(e)) if e == "disk hdb can't be attached to the ide controller, xl attaches every disk to the ahci controller of the domain"
+        ));
+    }
+
+    #[test]
+    fn test_disk_devices_name_collision() {
+        let disk_devices = DiskDevices(vec![
+            Disk {
+                virtual_device: "xvda".to_string(),
+                ..Default::default()
+            },
+            Disk {
+                virtual_device: "sda".to_string(),
+                ..Default::default()
+            },
+        ]);
+
+        assert!(matches!(
+            disk_devices.validate(),
+            Err(TemplateError::Validation(e)) if e == "disks xvda and sda use the same virtual device"
+        ));
+    }
+
+    #[test]
+    fn test_disk_devices_target_collision() {
+        let disk = |virtual_device: &str, target: &str| Disk {
+            target: PathBuf::from(target),
+            virtual_device: virtual_device.to_string(),
+            ..Default::default()
+        };
+
+        // Relative targets are normalized before being compared
+        let disk_devices = DiskDevices(vec![
+            disk("xvda", "/dev/sda"),
+            disk("xvdb", "sda"),
+            disk("xvdc", ""),
+            disk("xvdd", ""),
+        ]);
+        assert_eq!(
+            disk_devices.conflicts(),
+            vec![DiskConflict::Target(
+                "xvda".to_string(),
+                "xvdb".to_string(),
+                PathBuf::from("/dev/sda")
+            )]
+        );
+        assert!(matches!(
+            disk_devices.validate(),
+            Err(TemplateError::Validation(e)) if e == "disks xvda and xvdb use the same target /dev/sda"
+        ));
+
+        // All the conflicts are reported
+        let disk_devices = DiskDevices(vec![
+            disk("xvda", "/dev/sda"),
+            disk("sda", "/dev/sda"),
+            disk("xvdb", "/dev/sdb"),
+        ]);
+        assert_eq!(disk_devices.conflicts().len(), 2);
+        assert!(matches!(
+            disk_devices.validate(),
+            Err(TemplateError::Validation(e)) if e == "disks xvda and sda use the same virtual device, disks xvda and sda use the same target /dev/sda"
+        ));
+
+        assert!(
+            DiskDevices(vec![disk("xvda", "/dev/sda"), disk("xvdb", "/dev/sdb")])
+                .conflicts()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_sound_hardware_display() {
+        assert_eq!(format!("{}", SoundHardware::None), "none");
+        assert_eq!(format!("{}", SoundHardware::Hda), "hda");
+        assert_eq!(format!("{}", SoundHardware::Ac97), "ac97");
+        assert_eq!(format!("{}", SoundHardware::Sb16), "sb16");
+    }
+
+    #[test]
+    fn test_sound_hardware_xl_config() {
+        assert_eq!(SoundHardware::None.xl_config(), "");
+        assert_eq!(SoundHardware::Hda.xl_config(), "soundhw = \"hda\"");
+        assert_eq!(SoundHardware::Ac97.xl_config(), "soundhw = \"ac97\"");
+        assert_eq!(SoundHardware::Sb16.xl_config(), "soundhw = \"sb16\"");
+    }
+
+    #[test]
+    fn test_vga_model_display() {
+        assert_eq!(format!("{}", VgaModel::Stdvga), "stdvga");
+        assert_eq!(format!("{}", VgaModel::Cirrus), "cirrus");
+        assert_eq!(format!("{}", VgaModel::Qxl), "qxl");
+        assert_eq!(format!("{}", VgaModel::None), "none");
+    }
+
+    #[test]
+    fn test_video_adapter_display() {
+        let video_adapter = VideoAdapter {
+            model: VgaModel::Stdvga,
+            memory_mb: Some(32),
+        };
+        assert_eq!(format!("{}", video_adapter), "stdvga (32 MB)");
+
+        let video_adapter = VideoAdapter {
+            model: VgaModel::Cirrus,
+            memory_mb: None,
+        };
+        assert_eq!(format!("{}", video_adapter), "cirrus");
+    }
+
+    #[test]
+    fn test_video_adapter_xl_config() {
+        let video_adapter = VideoAdapter {
+            model: VgaModel::Stdvga,
+            memory_mb: Some(32),
+        };
+        assert_eq!(video_adapter.xl_config(), "vga = \"stdvga\"\nvideoram = 32");
+
+        let video_adapter = VideoAdapter {
+            model: VgaModel::Cirrus,
+            memory_mb: Some(8),
+        };
+        assert_eq!(video_adapter.xl_config(), "vga = \"cirrus\"\nvideoram = 8");
+
+        let video_adapter = VideoAdapter {
+            model: VgaModel::Qxl,
+            memory_mb: None,
+        };
+        assert_eq!(video_adapter.xl_config(), "vga = \"qxl\"");
+
+        let video_adapter = VideoAdapter {
+            model: VgaModel::None,
+            memory_mb: None,
+        };
+        assert_eq!(video_adapter.xl_config(), "vga = \"none\"");
+    }
+
+    #[test]
+    fn test_video_adapter_validate() {
+        let mut video_adapter = VideoAdapter {
+            model: VgaModel::Stdvga,
+            memory_mb: Some(16),
+        };
+        assert!(video_adapter.validate().is_ok());
+
+        video_adapter.memory_mb = Some(8);
+        assert!(matches!(
+            video_adapter.validate(),
+            Err(TemplateError::Validation(e)) if e == "video memory of stdvga must be between 16 and 256 MB"
+        ));
+
+        video_adapter.model = VgaModel::Qxl;
+        video_adapter.memory_mb = Some(512);
+        assert!(video_adapter.validate().is_err());
+
+        video_adapter.model = VgaModel::None;
+        video_adapter.memory_mb = Some(16);
+        assert!(video_adapter.validate().is_err());
+    }
+
+    #[test]
+    fn test_boot_device_serde_round_trip() {
+        for boot_device in [BootDevice::HardDisk, BootDevice::CdRom, BootDevice::Network] {
+            let serialized = serde_json::to_string(&boot_device).unwrap();
+            assert_eq!(
+                serde_json::from_str::<BootDevice>(&serialized).unwrap(),
+                boot_device
+            );
+        }
+    }
+
+    #[test]
+    fn test_emulated_disk_controller_type_serde_round_trip() {
+        for controller in [
+            EmulatedDiskControllerType::Ide,
+            EmulatedDiskControllerType::Ahci,
+        ] {
+            let serialized = serde_json::to_string(&controller).unwrap();
+            assert_eq!(
+                serde_json::from_str::<EmulatedDiskControllerType>(&serialized).unwrap(),
+                controller
+            );
+        }
+    }
 }
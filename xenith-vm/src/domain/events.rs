@@ -18,12 +18,15 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 //! Domain event structures and options for a domain.
 
+use serde::{Deserialize, Serialize};
+
 use std::fmt::Display;
 
 use crate::XlConfiguration;
+use crate::error::TemplateError;
 
 /// Represents the action to take when a domain event occurs
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum EventAction {
     /// Destroy the domain
     #[default]
@@ -63,7 +66,7 @@ impl Display for EventAction {
 }
 
 /// Represents the actions to take when a domain event occurs
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct DomainActions {
     /// Specifies what should be done with the domain if it shuts itself down.
     pub on_poweroff: EventAction,
@@ -89,6 +92,99 @@ impl Default for DomainActions {
     }
 }
 
+impl DomainActions {
+    /// Actions allowed when the domain shuts itself down. There is no crashed state to
+    /// dump or rename, and soft reset only applies to `kexec`.
+    pub const ALLOWED_ON_POWEROFF: &[EventAction] = &[
+        EventAction::Destroy,
+        EventAction::Restart,
+        EventAction::Preserve,
+    ];
+    /// Actions allowed when the domain requests a reboot
+    pub const ALLOWED_ON_REBOOT: &[EventAction] = &[
+        EventAction::Destroy,
+        EventAction::Restart,
+        EventAction::RenameRestart,
+        EventAction::Preserve,
+    ];
+    /// Actions allowed on a Xen watchdog timeout. A hung domain can't perform a soft
+    /// reset.
+    pub const ALLOWED_ON_WATCHDOG: &[EventAction] = &[
+        EventAction::Destroy,
+        EventAction::Restart,
+        EventAction::RenameRestart,
+        EventAction::Preserve,
+        EventAction::CoreDumpDestroy,
+        EventAction::CoreDumpRestart,
+    ];
+    /// Actions allowed when the domain crashes. A crashed domain can't perform a soft
+    /// reset.
+    pub const ALLOWED_ON_CRASH: &[EventAction] = &[
+        EventAction::Destroy,
+        EventAction::Restart,
+        EventAction::RenameRestart,
+        EventAction::Preserve,
+        EventAction::CoreDumpDestroy,
+        EventAction::CoreDumpRestart,
+    ];
+    /// Actions allowed when the domain performs a soft reset
+    pub const ALLOWED_ON_SOFT_RESET: &[EventAction] = &[
+        EventAction::Destroy,
+        EventAction::Restart,
+        EventAction::Preserve,
+        EventAction::SoftReset,
+    ];
+
+    /// Validate the action of each event
+    ///
+    /// `xl` accepts any action for any event, this matrix rejects the combinations which
+    /// make no sense for the event, e.g. `rename-restart` on `on_poweroff`.
+    ///
+    /// | Action             | poweroff | reboot | watchdog | crash | soft_reset |
+    /// |--------------------|----------|--------|----------|-------|------------|
+    /// | `destroy`          | yes      | yes    | yes      | yes   | yes        |
+    /// | `restart`          | yes      | yes    | yes      | yes   | yes        |
+    /// | `rename-restart`   | no       | yes    | yes      | yes   | no         |
+    /// | `preserve`         | yes      | yes    | yes      | yes   | yes        |
+    /// | `coredump-destroy` | no       | no     | yes      | yes   | no         |
+    /// | `coredump-restart` | no       | no     | yes      | yes   | no         |
+    /// | `soft-reset`       | no       | no     | no       | no    | yes        |
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] for the first illegal event action
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        let events = [
+            ("on_poweroff", &self.on_poweroff, Self::ALLOWED_ON_POWEROFF),
+            ("on_reboot", &self.on_reboot, Self::ALLOWED_ON_REBOOT),
+            ("on_watchdog", &self.on_watchdog, Self::ALLOWED_ON_WATCHDOG),
+            ("on_crash", &self.on_crash, Self::ALLOWED_ON_CRASH),
+            (
+                "on_soft_reset",
+                &self.on_soft_reset,
+                Self::ALLOWED_ON_SOFT_RESET,
+            ),
+        ];
+
+        for (event, action, allowed) in events {
+            if !allowed.contains(action) {
+                let allowed = allowed
+                    .iter()
+                    .map(|action| action.to_string())
+                    .collect::<Vec<String>>();
+                return Err(TemplateError::Validation(format!(
+                    "action {} is not allowed for {}, expected one of: {}",
+                    action,
+                    event,
+                    allowed.join(", ")
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl Display for DomainActions {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -106,10 +202,136 @@ impl XlConfiguration for DomainActions {
     }
 }
 
+/// Represents the model of watchdog device provided to the guest
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum WatchdogModel {
+    /// Paravirtualized watchdog provided by Xen itself (`SCHEDOP_watchdog`), used by the
+    /// guest `xen_wdt` driver. It triggers the `on_watchdog` action.
+    #[default]
+    Xen,
+    /// Intel 6300ESB watchdog emulated by the device model, supported by most guests.
+    ///
+    /// ⚠️ Only available for HVM guests.
+    I6300esb,
+    /// iBase IB700 watchdog emulated by the device model.
+    ///
+    /// ⚠️ Only available for HVM guests.
+    Ib700,
+}
+
+impl Display for WatchdogModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchdogModel::Xen => write!(f, "xen"),
+            WatchdogModel::I6300esb => write!(f, "i6300esb"),
+            WatchdogModel::Ib700 => write!(f, "ib700"),
+        }
+    }
+}
+
+/// Represents the watchdog device of a domain
+///
+/// The watchdog resets, destroys or preserves the domain when the guest stops
+/// feeding it, e.g. after a hang.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct Watchdog {
+    /// The model of watchdog device
+    pub model: WatchdogModel,
+    /// Action to take when the watchdog expires. For the Xen watchdog, this replaces
+    /// [`DomainActions::on_watchdog`].
+    pub action: EventAction,
+}
+
+impl Watchdog {
+    /// Validate the watchdog configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if the action is not supported by the
+    /// watchdog model. The Xen watchdog accepts any action but a soft reset, while
+    /// emulated watchdogs can only destroy, restart or preserve (pause) the domain.
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        let supported = match self.model {
+            WatchdogModel::Xen => self.action != EventAction::SoftReset,
+            WatchdogModel::I6300esb | WatchdogModel::Ib700 => self.device_model_action().is_some(),
+        };
+
+        if !supported {
+            return Err(TemplateError::Validation(format!(
+                "watchdog {} doesn't support the {} action",
+                self.model, self.action
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Get the device model action matching the watchdog action
+    ///
+    /// # Returns
+    ///
+    /// The QEMU watchdog action, or `None` if QEMU has no equivalent
+    fn device_model_action(&self) -> Option<&'static str> {
+        match self.action {
+            EventAction::Destroy => Some("poweroff"),
+            EventAction::Restart => Some("reset"),
+            EventAction::Preserve => Some("pause"),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Watchdog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "model={}, action={}", self.model, self.action)
+    }
+}
+
+impl XlConfiguration for Watchdog {
+    // The Xen watchdog doesn't need a device, its action is rendered with `on_watchdog`
+    // device_model_args_hvm=[ "-device", "MODEL", "-action", "watchdog=ACTION" ]
+    fn xl_config(&self) -> String {
+        match (&self.model, self.device_model_action()) {
+            (WatchdogModel::Xen, _) | (_, None) => String::new(),
+            (model, Some(action)) => format!(
+                "device_model_args_hvm = [ \"-device\", \"{}\", \"-action\", \"watchdog={}\" ]",
+                model, action
+            ),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_domain_actions_validate() {
+        assert!(DomainActions::default().validate().is_ok());
+
+        let domain_actions = DomainActions {
+            on_crash: EventAction::CoreDumpRestart,
+            on_reboot: EventAction::RenameRestart,
+            ..Default::default()
+        };
+        assert!(domain_actions.validate().is_ok());
+
+        let domain_actions = DomainActions {
+            on_poweroff: EventAction::RenameRestart,
+            ..Default::default()
+        };
+        assert!(matches!(
+            domain_actions.validate(),
+            Err(TemplateError::Validation(e)) if e == "action rename-restart is not allowed for on_poweroff, expected one of: destroy, restart, preserve"
+        ));
+
+        let domain_actions = DomainActions {
+            on_crash: EventAction::SoftReset,
+            ..Default::default()
+        };
+        assert!(domain_actions.validate().is_err());
+    }
+
     #[test]
     fn test_event_action_display() {
         assert_eq!(EventAction::Destroy.to_string(), "destroy");
@@ -152,4 +374,74 @@ mod tests {
             "on_poweroff = \"destroy\"\non_reboot = \"restart\"\non_watchdog = \"destroy\"\non_crash = \"destroy\"\non_soft_reset = \"soft-reset\""
         );
     }
+
+    #[test]
+    fn test_watchdog_model_display() {
+        assert_eq!(WatchdogModel::Xen.to_string(), "xen");
+        assert_eq!(WatchdogModel::I6300esb.to_string(), "i6300esb");
+        assert_eq!(WatchdogModel::Ib700.to_string(), "ib700");
+    }
+
+    #[test]
+    fn test_watchdog_display() {
+        let watchdog = Watchdog {
+            model: WatchdogModel::I6300esb,
+            action: EventAction::Restart,
+        };
+        assert_eq!(watchdog.to_string(), "model=i6300esb, action=restart");
+    }
+
+    #[test]
+    fn test_watchdog_xl_config() {
+        let watchdog = Watchdog {
+            model: WatchdogModel::Xen,
+            action: EventAction::CoreDumpRestart,
+        };
+        assert_eq!(watchdog.xl_config(), "");
+
+        let watchdog = Watchdog {
+            model: WatchdogModel::I6300esb,
+            action: EventAction::Restart,
+        };
+        assert_eq!(
+            watchdog.xl_config(),
+            "device_model_args_hvm = [ \"-device\", \"i6300esb\", \"-action\", \"watchdog=reset\" ]"
+        );
+
+        let watchdog = Watchdog {
+            model: WatchdogModel::Ib700,
+            action: EventAction::Preserve,
+        };
+        assert_eq!(
+            watchdog.xl_config(),
+            "device_model_args_hvm = [ \"-device\", \"ib700\", \"-action\", \"watchdog=pause\" ]"
+        );
+    }
+
+    #[test]
+    fn test_watchdog_validate() {
+        let watchdog = Watchdog {
+            model: WatchdogModel::Xen,
+            action: EventAction::CoreDumpDestroy,
+        };
+        assert!(watchdog.validate().is_ok());
+
+        let watchdog = Watchdog {
+            model: WatchdogModel::Xen,
+            action: EventAction::SoftReset,
+        };
+        assert!(matches!(
+            watchdog.validate(),
+            Err(TemplateError::Validation(e)) if e == "watchdog xen doesn't support the soft-reset action"
+        ));
+
+        let watchdog = Watchdog {
+            model: WatchdogModel::I6300esb,
+            action: EventAction::RenameRestart,
+        };
+        assert!(matches!(
+            watchdog.validate(),
+            Err(TemplateError::Validation(_))
+        ));
+    }
 }
@@ -0,0 +1,99 @@
+/*
+Xenith - Xen-based security hypervisor
+Copyright (C) 2025 Xenith contributors
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Memory configuration structures and options for a domain.
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt::Display;
+
+use crate::error::TemplateError;
+
+/// Represents the backing used for the guest memory
+///
+/// Large pages reduce the pressure on the TLB and the cost of the second level
+/// address translation (EPT/NPT), which benefits memory-intensive guests.
+///
+/// ⚠️ This option is not supported by `xl`: there is no `xl.cfg` key to request the page
+/// size, so only [`MemoryBacking::Default`] passes [`MemoryBacking::validate`]. Xen backs the
+/// guest memory with 2MB and 1GB superpages by itself whenever its heap has free contiguous
+/// memory, which is best ensured by giving enough memory to Xen at boot, e.g. with a fixed
+/// `dom0_mem`. Hugepages reserved in dom0 with `vm.nr_hugepages` or the
+/// `hugepagesz=2M hugepages=N` kernel parameters are not used for guests.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum MemoryBacking {
+    /// Let Xen choose how the guest memory is backed. This is the default and
+    /// does not emit any configuration key.
+    #[default]
+    Default,
+    /// Back the guest memory with 2MB pages.
+    HugePages2M,
+    /// Back the guest memory with 1GB pages.
+    HugePages1G,
+}
+
+impl Display for MemoryBacking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemoryBacking::Default => write!(f, "default"),
+            MemoryBacking::HugePages2M => write!(f, "2M"),
+            MemoryBacking::HugePages1G => write!(f, "1G"),
+        }
+    }
+}
+
+impl MemoryBacking {
+    /// Validate the memory backing
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if the backing is not
+    /// [`MemoryBacking::Default`], as `xl` can't request the page size of the guest memory
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        if *self != MemoryBacking::Default {
+            return Err(TemplateError::Validation(format!(
+                "the {} memory backing is not supported by xl",
+                self
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_backing_validate() {
+        assert!(MemoryBacking::Default.validate().is_ok());
+        assert!(matches!(
+            MemoryBacking::HugePages2M.validate(),
+            Err(TemplateError::Validation(e)) if e == "the 2M memory backing is not supported by xl"
+        ));
+        assert!(MemoryBacking::HugePages1G.validate().is_err());
+    }
+
+    #[test]
+    fn test_memory_backing_display() {
+        assert_eq!(MemoryBacking::Default.to_string(), "default");
+        assert_eq!(MemoryBacking::HugePages2M.to_string(), "2M");
+        assert_eq!(MemoryBacking::HugePages1G.to_string(), "1G");
+    }
+}
@@ -18,14 +18,21 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 //! Network configuration structures and options for a domain.
 
+use serde::{Deserialize, Serialize};
+
 use std::fmt::Display;
+use std::net::IpAddr;
+use std::path::Path;
 
+pub use ipnet::IpNet;
 pub use mac_address::MacAddress;
 
 use crate::XlConfiguration;
+use crate::domain::DomainName;
+use crate::error::{NetworkError, TemplateError};
 
 /// Represents the type of network interface attached to a virtual machine
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum NetworkInterfaceType {
     /// Device will be provided as an emulate device to the
     /// guest and also as a paravirtualised device which the guest may choose to use
@@ -45,9 +52,39 @@ impl Display for NetworkInterfaceType {
     }
 }
 
+/// Represents how the network interface is connected to the host network
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum NetworkMode {
+    /// The interface is added to a bridge on the host, using the `vif-bridge` hotplug script.
+    #[default]
+    Bridge,
+    /// The traffic of the interface is routed through the gateway device of the host, using
+    /// the `vif-route` hotplug script.
+    Route,
+}
+
+impl NetworkMode {
+    /// Get the hotplug script used to set up the interface on the host
+    pub fn script(&self) -> &'static str {
+        match self {
+            NetworkMode::Bridge => "vif-bridge",
+            NetworkMode::Route => "vif-route",
+        }
+    }
+}
+
+impl Display for NetworkMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkMode::Bridge => write!(f, "bridge"),
+            NetworkMode::Route => write!(f, "route"),
+        }
+    }
+}
+
 /// Represents the model of network interface to use
 /// This is only available for HVM guests.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum NetworkInterfaceModel {
     /// Realtek RTL8139
     #[default]
@@ -75,7 +112,7 @@ impl Display for NetworkInterfaceModel {
 /// device.
 ///
 /// See `man xl-network-configuration` for more information.
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct NetworkInterface {
     /// Specifies the backend device name for the virtual device.
     /// If the domain is an HVM domain then the associated emulated (tap) device will have a
@@ -122,7 +159,17 @@ pub struct NetworkInterface {
     /// network the VIF should communicate with. This is used in the host by the vif-route
     /// hotplug script. See [wiki](https://wiki.xenproject.org/wiki/Vif-route) for guidance
     /// and examples.
+    ///
+    /// Only used in [`NetworkMode::Route`] mode.
     pub gateway_device: String,
+    /// IP addresses of the VIF device, used by the hotplug scripts to set up the routes and
+    /// the anti-spoofing rules of the host. IPv4 and IPv6 addresses can be mixed for dual-stack
+    /// interfaces.
+    ///
+    /// The default is to not restrict the addresses of the guest.
+    pub ip_addresses: Vec<IpNet>,
+    /// Specifies whether the interface is bridged or routed, which selects the hotplug script.
+    pub mode: NetworkMode,
     /// The type of network interface to use.
     /// ⚠️ Only available for HVM guests.
     pub r#type: NetworkInterfaceType,
@@ -130,6 +177,9 @@ pub struct NetworkInterface {
     /// Only valid if `type` is `IoEmu`.
     /// ⚠️ Only available for HVM guests.
     pub model: Option<NetworkInterfaceModel>,
+    /// Name of the driver domain running the network backend. When `None`, the backend
+    /// runs in dom0.
+    pub backend_domain: Option<String>,
 }
 
 impl Default for NetworkInterface {
@@ -139,30 +189,275 @@ impl Default for NetworkInterface {
             mac: MacAddress::default(),
             bridge: "xenbr0".to_string(),
             gateway_device: String::default(),
+            ip_addresses: Vec::new(),
+            mode: NetworkMode::default(),
             r#type: NetworkInterfaceType::default(),
             model: Some(NetworkInterfaceModel::Rtl8139),
+            backend_domain: None,
+        }
+    }
+}
+
+impl NetworkInterface {
+    /// Path where the host network interfaces are exposed by sysfs
+    pub const SYSFS_NET_PATH: &str = "/sys/class/net";
+
+    /// Xen's Organizationally Unique Identifier
+    pub const XEN_OUI: [u8; 3] = [0x00, 0x16, 0x3e];
+
+    /// Locally administered bit of the first byte of a MAC address
+    const LOCALLY_ADMINISTERED_BIT: u8 = 0x02;
+
+    /// Derive the default name of a VIF device, as chosen by the toolstack
+    ///
+    /// The name is `vifDOMID.DEVID`, and `vifDOMID.DEVID-emu` for the emulated (tap) device
+    /// of an HVM domain. It can be used to find the host devices of a running domain, the
+    /// domain ID being only known once it is created.
+    ///
+    /// # Arguments
+    ///
+    /// * `domid` - The ID of the domain owning the interface
+    /// * `devid` - The device number of the interface in the domain
+    /// * `hvm` - Whether the name of the emulated device of an HVM domain is derived
+    pub fn derive_name(domid: u32, devid: u8, hvm: bool) -> String {
+        let name = format!("vif{}.{}", domid, devid);
+        match hvm {
+            true => format!("{}-emu", name),
+            false => name,
+        }
+    }
+
+    /// Derive a stable MAC address from a domain name and an interface index.
+    ///
+    /// The name and index are hashed with FNV-1a, whose output does not depend on the
+    /// Rust release unlike the standard library hasher, and the low 24 bits of the hash
+    /// fill the NIC specific part of the address. The prefix is Xen's OUI with the
+    /// locally administered bit set (`02:16:3e`), so the address cannot clash with
+    /// the ones generated by the toolstack inside `00:16:3e`.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain_name` - The name of the domain owning the interface
+    /// * `index` - The index of the interface in the domain
+    pub fn deterministic_mac(domain_name: &str, index: u8) -> MacAddress {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+        const FNV_PRIME: u64 = 0x100000001b3;
+
+        let hash = domain_name
+            .bytes()
+            .chain([0, index])
+            .fold(FNV_OFFSET_BASIS, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+            });
+        // Fold the upper half in so every input bit affects the kept bytes
+        let hash = (hash ^ (hash >> 32)).to_be_bytes();
+
+        MacAddress::new([
+            NetworkInterface::XEN_OUI[0] | NetworkInterface::LOCALLY_ADMINISTERED_BIT,
+            NetworkInterface::XEN_OUI[1],
+            NetworkInterface::XEN_OUI[2],
+            hash[5],
+            hash[6],
+            hash[7],
+        ])
+    }
+
+    /// Parse an IP address of the interface
+    ///
+    /// Both IPv4 and IPv6 addresses are accepted, with or without a prefix length in CIDR
+    /// notation. An address without a prefix length is a single host address (`/32` or `/128`).
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to parse, e.g. `10.0.0.2/24` or `2001:db8::2`
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if the address is invalid
+    pub fn parse_ip_address(address: &str) -> Result<IpNet, TemplateError> {
+        address
+            .parse::<IpNet>()
+            .or_else(|_| address.parse::<IpAddr>().map(IpNet::from))
+            .map_err(|_| TemplateError::Validation(format!("invalid IP address \"{}\"", address)))
+    }
+
+    /// Validate the network interface
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if the backend domain name is invalid
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        if let Some(backend_domain) = &self.backend_domain {
+            DomainName(backend_domain.clone()).validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Check that the bridge of the interface exists on the host
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`NetworkError`] if the bridge does not exist or is not a bridge
+    pub fn validate_bridge(&self) -> Result<(), NetworkError> {
+        self.validate_bridge_in(Path::new(NetworkInterface::SYSFS_NET_PATH))
+    }
+
+    /// Check that the bridge of the interface exists, looking for network interfaces
+    /// in the given sysfs directory
+    ///
+    /// # Arguments
+    ///
+    /// * `sysfs_net_path` - The directory listing the network interfaces, usually `/sys/class/net`
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`NetworkError`] if the bridge does not exist or is not a bridge
+    pub fn validate_bridge_in(&self, sysfs_net_path: &Path) -> Result<(), NetworkError> {
+        let interface = sysfs_net_path.join(&self.bridge);
+        if !interface.exists() {
+            return Err(NetworkError::BridgeNotFound(self.bridge.clone()));
+        }
+
+        // Bridges expose their attributes in a `bridge` subdirectory
+        if !interface.join("bridge").is_dir() {
+            return Err(NetworkError::NotABridge(self.bridge.clone()));
         }
+
+        Ok(())
     }
 }
 
 impl Display for NetworkInterface {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "mac={}, bridge={}, gatewaydev={}, type={}, model={}",
-            self.mac,
-            self.bridge,
-            self.gateway_device,
-            self.r#type,
-            self.model.as_ref().unwrap()
-        )
+        let mut spec = vec![format!("mac={}", self.mac)];
+
+        match self.mode {
+            NetworkMode::Bridge => spec.push(format!("bridge={}", self.bridge)),
+            NetworkMode::Route if !self.gateway_device.is_empty() => {
+                spec.push(format!("gatewaydev={}", self.gateway_device))
+            }
+            NetworkMode::Route => {}
+        }
+
+        if !self.ip_addresses.is_empty() {
+            // The hotplug scripts split the addresses on spaces
+            let addresses = self
+                .ip_addresses
+                .iter()
+                .map(IpNet::to_string)
+                .collect::<Vec<String>>();
+            spec.push(format!("ip={}", addresses.join(" ")));
+        }
+
+        spec.push(format!("script={}", self.mode.script()));
+        spec.push(format!("type={}", self.r#type));
+        if let Some(model) = &self.model {
+            spec.push(format!("model={}", model));
+        }
+        if let Some(backend_domain) = &self.backend_domain {
+            spec.push(format!("backend={}", backend_domain));
+        }
+
+        write!(f, "{}", spec.join(", "))
+    }
+}
+
+/// List the bridges of the host, sorted by name
+///
+/// On Linux, bridges are the network interfaces exposing a `bridge` directory in sysfs.
+/// Other platforms are not supported, an empty list is returned with a warning.
+///
+/// # Errors
+///
+/// Returns a [`NetworkError::InterfaceListing`] if the network interfaces can't be listed
+pub fn list_host_bridges() -> Result<Vec<String>, NetworkError> {
+    #[cfg(target_os = "linux")]
+    {
+        list_host_bridges_in(Path::new(NetworkInterface::SYSFS_NET_PATH))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        log::warn!("Listing host bridges is only supported on Linux");
+        Ok(Vec::new())
+    }
+}
+
+/// List the bridges of the host, looking for network interfaces in the given sysfs directory
+///
+/// # Arguments
+///
+/// * `sysfs_net_path` - The directory listing the network interfaces, usually `/sys/class/net`
+///
+/// # Errors
+///
+/// Returns a [`NetworkError::InterfaceListing`] if the directory can't be read
+pub fn list_host_bridges_in(sysfs_net_path: &Path) -> Result<Vec<String>, NetworkError> {
+    let entries = std::fs::read_dir(sysfs_net_path)
+        .map_err(|e| NetworkError::InterfaceListing(e.to_string()))?;
+
+    let mut bridges = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| NetworkError::InterfaceListing(e.to_string()))?;
+        if entry.path().join("bridge").is_dir() {
+            bridges.push(entry.file_name().to_string_lossy().into_owned());
+        }
     }
+    bridges.sort();
+
+    Ok(bridges)
 }
 
 /// Represents a list of network interfaces attached to a domain.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct NetworkInterfaces(pub Vec<NetworkInterface>);
 
+impl NetworkInterfaces {
+    /// Attach a network interface
+    pub fn push(&mut self, network_interface: NetworkInterface) {
+        self.0.push(network_interface);
+    }
+
+    /// Iterate over the network interfaces
+    pub fn iter(&self) -> std::slice::Iter<'_, NetworkInterface> {
+        self.0.iter()
+    }
+
+    /// Get the number of network interfaces
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if there is no network interface
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromIterator<NetworkInterface> for NetworkInterfaces {
+    fn from_iter<I: IntoIterator<Item = NetworkInterface>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for NetworkInterfaces {
+    type Item = NetworkInterface;
+    type IntoIter = std::vec::IntoIter<NetworkInterface>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a NetworkInterfaces {
+    type Item = &'a NetworkInterface;
+    type IntoIter = std::slice::Iter<'a, NetworkInterface>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
 impl XlConfiguration for NetworkInterfaces {
     // vif=[ "NET_SPEC_STRING", "NET_SPEC_STRING", ...]
     // where each vifspec is in this form: [<key>=<value>|<flag>,]
@@ -199,20 +494,177 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_network_mode_display() {
+        assert_eq!(NetworkMode::Bridge.to_string(), "bridge");
+        assert_eq!(NetworkMode::Route.to_string(), "route");
+        assert_eq!(NetworkMode::Bridge.script(), "vif-bridge");
+        assert_eq!(NetworkMode::Route.script(), "vif-route");
+    }
+
+    #[test]
+    fn test_routed_network_interface_display() {
+        let mut network_interface = NetworkInterface {
+            name: "vif0.0".to_string(),
+            mac: MacAddress::from_str("00:16:3e:00:00:00").unwrap(),
+            bridge: "xenbr0".to_string(),
+            gateway_device: "eth0".to_string(),
+            ip_addresses: Vec::new(),
+            mode: NetworkMode::Route,
+            r#type: NetworkInterfaceType::Vif,
+            model: None,
+            backend_domain: None,
+        };
+        assert_eq!(
+            network_interface.to_string(),
+            "mac=00:16:3E:00:00:00, gatewaydev=eth0, script=vif-route, type=vif"
+        );
+
+        network_interface.gateway_device = String::new();
+        assert_eq!(
+            network_interface.to_string(),
+            "mac=00:16:3E:00:00:00, script=vif-route, type=vif"
+        );
+    }
+
     #[test]
     fn test_network_interface_display() {
         let network_interface = NetworkInterface {
             name: "vif0.0".to_string(),
             mac: MacAddress::from_str("00:16:3e:00:00:00").unwrap(),
             bridge: "xenbr0".to_string(),
-            gateway_device: "eth0".to_string(),
+            gateway_device: String::new(),
+            ip_addresses: Vec::new(),
+            mode: NetworkMode::Bridge,
             r#type: NetworkInterfaceType::IoEmu,
             model: Some(NetworkInterfaceModel::Rtl8139),
+            backend_domain: None,
+        };
+        assert_eq!(
+            network_interface.to_string(),
+            "mac=00:16:3E:00:00:00, bridge=xenbr0, script=vif-bridge, type=ioemu, model=rtl8139"
+        );
+    }
+
+    #[test]
+    fn test_network_interface_backend_domain() {
+        let mut network_interface = NetworkInterface {
+            mac: MacAddress::from_str("00:16:3e:00:00:00").unwrap(),
+            backend_domain: Some("net-driver".to_string()),
+            ..Default::default()
+        };
+        assert!(network_interface.validate().is_ok());
+        assert_eq!(
+            network_interface.to_string(),
+            "mac=00:16:3E:00:00:00, bridge=xenbr0, script=vif-bridge, type=ioemu, model=rtl8139, backend=net-driver"
+        );
+
+        network_interface.backend_domain = Some("net driver".to_string());
+        assert!(matches!(
+            network_interface.validate(),
+            Err(TemplateError::Validation(e)) if e == "invalid domain name \"net driver\""
+        ));
+    }
+
+    #[test]
+    fn test_network_interface_ip_addresses() {
+        let mut network_interface = NetworkInterface {
+            mac: MacAddress::from_str("00:16:3e:00:00:00").unwrap(),
+            gateway_device: "eth0".to_string(),
+            mode: NetworkMode::Route,
+            model: None,
+            ..Default::default()
         };
+
+        network_interface.ip_addresses =
+            vec![NetworkInterface::parse_ip_address("10.0.0.2/24").unwrap()];
         assert_eq!(
             network_interface.to_string(),
-            "mac=00:16:3E:00:00:00, bridge=xenbr0, gatewaydev=eth0, type=ioemu, model=rtl8139"
+            "mac=00:16:3E:00:00:00, gatewaydev=eth0, ip=10.0.0.2/24, script=vif-route, type=ioemu"
         );
+
+        network_interface.ip_addresses =
+            vec![NetworkInterface::parse_ip_address("2001:db8::2").unwrap()];
+        assert_eq!(
+            network_interface.to_string(),
+            "mac=00:16:3E:00:00:00, gatewaydev=eth0, ip=2001:db8::2/128, script=vif-route, type=ioemu"
+        );
+
+        network_interface.ip_addresses = vec![
+            NetworkInterface::parse_ip_address("10.0.0.2").unwrap(),
+            NetworkInterface::parse_ip_address("2001:db8::2/64").unwrap(),
+        ];
+        assert_eq!(
+            network_interface.to_string(),
+            "mac=00:16:3E:00:00:00, gatewaydev=eth0, ip=10.0.0.2/32 2001:db8::2/64, script=vif-route, type=ioemu"
+        );
+
+        assert!(matches!(
+            NetworkInterface::parse_ip_address("10.0.0.256"),
+            Err(TemplateError::Validation(e)) if e == "invalid IP address \"10.0.0.256\""
+        ));
+        assert!(NetworkInterface::parse_ip_address("2001:db8::2/129").is_err());
+    }
+
+    #[test]
+    fn test_network_interface_serde() {
+        let network_interface = NetworkInterface {
+            mac: MacAddress::from_str("00:16:3e:00:00:01").unwrap(),
+            ip_addresses: vec![NetworkInterface::parse_ip_address("2001:db8::2/64").unwrap()],
+            ..Default::default()
+        };
+
+        let serialized = serde_json::to_value(&network_interface).unwrap();
+        // The MAC address is serialized in its canonical form
+        assert_eq!(serialized["mac"], "00:16:3E:00:00:01");
+        assert_eq!(serialized["ip_addresses"][0], "2001:db8::2/64");
+        assert_eq!(
+            serde_json::from_value::<NetworkInterface>(serialized.clone()).unwrap(),
+            network_interface
+        );
+
+        let mut invalid = serialized;
+        invalid["mac"] = "00:16:3e:00:00".into();
+        assert!(serde_json::from_value::<NetworkInterface>(invalid).is_err());
+    }
+
+    #[test]
+    fn test_network_interface_derive_name() {
+        assert_eq!(NetworkInterface::derive_name(0, 0, false), "vif0.0");
+        assert_eq!(NetworkInterface::derive_name(12, 3, false), "vif12.3");
+        assert_eq!(NetworkInterface::derive_name(12, 3, true), "vif12.3-emu");
+    }
+
+    #[test]
+    fn test_network_interface_deterministic_mac() {
+        let mac = NetworkInterface::deterministic_mac("xenith", 0);
+        assert_eq!(mac, NetworkInterface::deterministic_mac("xenith", 0));
+        assert_eq!(mac.bytes()[..3], [0x02, 0x16, 0x3e]);
+        // Locally administered, unicast
+        assert_eq!(mac.bytes()[0] & 0x03, 0x02);
+
+        assert_ne!(mac, NetworkInterface::deterministic_mac("xenith", 1));
+        assert_ne!(mac, NetworkInterface::deterministic_mac("xenith2", 0));
+    }
+
+    #[test]
+    fn test_network_interfaces_collection() {
+        let mut network_interfaces = (0..2)
+            .map(|index| NetworkInterface {
+                mac: NetworkInterface::deterministic_mac("xenith", index),
+                ..Default::default()
+            })
+            .collect::<NetworkInterfaces>();
+        assert_eq!(network_interfaces.len(), 2);
+
+        network_interfaces.push(NetworkInterface::default());
+        assert_eq!(network_interfaces.iter().count(), 3);
+        assert!(
+            (&network_interfaces)
+                .into_iter()
+                .all(|network_interface| network_interface.bridge == "xenbr0")
+        );
+        assert!(NetworkInterfaces::default().is_empty());
     }
 
     #[test]
@@ -222,23 +674,73 @@ mod tests {
                 name: "vif0.0".to_string(),
                 mac: MacAddress::from_str("00:16:3e:00:00:00").unwrap(),
                 bridge: "xenbr0".to_string(),
-                gateway_device: "eth0".to_string(),
+                gateway_device: String::new(),
+                ip_addresses: Vec::new(),
+                mode: NetworkMode::Bridge,
                 r#type: NetworkInterfaceType::IoEmu,
                 model: Some(NetworkInterfaceModel::Rtl8139),
+                backend_domain: None,
             },
             NetworkInterface {
                 name: "vif0.1".to_string(),
                 mac: MacAddress::from_str("00:16:3e:00:00:01").unwrap(),
                 bridge: "xenbr0".to_string(),
-                gateway_device: "eth0".to_string(),
+                gateway_device: String::new(),
+                ip_addresses: Vec::new(),
+                mode: NetworkMode::Bridge,
                 r#type: NetworkInterfaceType::IoEmu,
                 model: Some(NetworkInterfaceModel::Rtl8139),
+                backend_domain: None,
             },
         ]);
 
         assert_eq!(
             network_interfaces.xl_config(),
-            "vif = [ \"mac=00:16:3E:00:00:00, bridge=xenbr0, gatewaydev=eth0, type=ioemu, model=rtl8139\", \"mac=00:16:3E:00:00:01, bridge=xenbr0, gatewaydev=eth0, type=ioemu, model=rtl8139\" ]"
+            "vif = [ \"mac=00:16:3E:00:00:00, bridge=xenbr0, script=vif-bridge, type=ioemu, model=rtl8139\", \"mac=00:16:3E:00:00:01, bridge=xenbr0, script=vif-bridge, type=ioemu, model=rtl8139\" ]"
+        );
+    }
+
+    #[test]
+    fn test_network_interface_validate_bridge() {
+        let sysfs = std::env::temp_dir().join(format!("xenith-sysfs-{}", std::process::id()));
+        std::fs::create_dir_all(sysfs.join("xenbr0/bridge")).unwrap();
+        std::fs::create_dir_all(sysfs.join("eth0")).unwrap();
+
+        let mut network_interface = NetworkInterface::default();
+        assert_eq!(network_interface.validate_bridge_in(&sysfs), Ok(()));
+
+        network_interface.bridge = "eth0".to_string();
+        assert_eq!(
+            network_interface.validate_bridge_in(&sysfs),
+            Err(NetworkError::NotABridge("eth0".to_string()))
+        );
+
+        network_interface.bridge = "xenbr1".to_string();
+        assert_eq!(
+            network_interface.validate_bridge_in(&sysfs),
+            Err(NetworkError::BridgeNotFound("xenbr1".to_string()))
+        );
+
+        std::fs::remove_dir_all(sysfs).unwrap();
+    }
+
+    #[test]
+    fn test_list_host_bridges() {
+        let sysfs =
+            std::env::temp_dir().join(format!("xenith-sysfs-bridges-{}", std::process::id()));
+        std::fs::create_dir_all(sysfs.join("xenbr1/bridge")).unwrap();
+        std::fs::create_dir_all(sysfs.join("xenbr0/bridge")).unwrap();
+        std::fs::create_dir_all(sysfs.join("eth0")).unwrap();
+
+        assert_eq!(
+            list_host_bridges_in(&sysfs),
+            Ok(vec!["xenbr0".to_string(), "xenbr1".to_string()])
         );
+
+        std::fs::remove_dir_all(&sysfs).unwrap();
+        assert!(matches!(
+            list_host_bridges_in(&sysfs),
+            Err(NetworkError::InterfaceListing(_))
+        ));
     }
 }
@@ -28,17 +28,94 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 //! This is an important limitation, as we need to hook the hypervisor leaves to hide the fact that
 //! the CPU is running in a virtual machine. To do this, we need to specify the full leaf value.
 //!
-//! If this changes, we could add it back with the `Xend` format, as the `Libxl` format does not allow
-//! to specify the full leaf value.
+//! The hypervisor vendor of a [`CpuModel`] is thus only rendered with the [`XlDialect::Xend`]
+//! dialect, for toolstacks processing the full leaf value, as the `Libxl` one does not allow to
+//! specify it.
 //!
 //! </div>
 
 use crate::XlConfiguration;
+use crate::domain::MaximumVirtualCpuNumber;
+use crate::error::TemplateError;
+
+use serde::{Deserialize, Serialize};
 
 use std::fmt::Display;
+use std::path::Path;
+
+/// Generate a CPUID register policy in the `Xend` format
+///
+/// Each character of the policy represents a bit of the register, from bit 31 to bit 0:
+/// bits in `mask` are forced to their value, other ones are left to Xen (`x`).
+///
+/// # Arguments
+///
+/// * `value` - The value of the register
+/// * `mask` - The bits of the register to override
+///
+/// # Returns
+///
+/// The 32 characters policy of the register
+pub fn cpuid_register_policy(value: u32, mask: u32) -> String {
+    (0..32)
+        .rev()
+        .map(|bit| match (mask >> bit & 1, value >> bit & 1) {
+            (0, _) => 'x',
+            (_, 0) => '0',
+            _ => '1',
+        })
+        .collect()
+}
+
+/// Generate the xl `cpuid` option from a list of CPUID leaf policies
+///
+/// # Arguments
+///
+/// * `policies` - The leaf policies in the `Xend` format, e.g. `1:ebx=xxxx...`
+///
+/// # Returns
+///
+/// The `cpuid` option, or an empty string if there is no policy
+pub fn cpuid_xl_config(policies: &[String]) -> String {
+    if policies.is_empty() {
+        return String::new();
+    }
+
+    let policies = policies
+        .iter()
+        .map(|policy| format!("\"{}\"", policy))
+        .collect::<Vec<String>>();
+    format!("cpuid = [ {} ]", policies.join(", "))
+}
+
+/// Represents the dialect of the rendered `xl.cfg`, i.e. the toolstack it targets
+#[derive(
+    Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub enum XlDialect {
+    /// Configuration processed by libxl, hypervisor CPUID leaves can't be overridden
+    #[default]
+    Libxl,
+    /// Configuration processed with the legacy `Xend` semantics, the full value of the
+    /// hypervisor CPUID leaves is rendered
+    ///
+    /// ⚠️ Xend was removed in Xen 4.5 and no current toolstack applies the hypervisor leaves:
+    /// libxl and Xen generate them and ignore policies for them. The hypervisor leaves are
+    /// therefore rejected by [`CpuModel::validate_dialect`] in this dialect as well.
+    Xend,
+}
+
+impl Display for XlDialect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XlDialect::Libxl => write!(f, "libxl"),
+            XlDialect::Xend => write!(f, "xend"),
+        }
+    }
+}
 
 /// Represents the access mode to the alternate-p2m capability
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum AlternateP2mMode {
     /// Altp2m is disabled for the domain
     #[default]
@@ -71,8 +148,274 @@ impl XlConfiguration for AlternateP2mMode {
     }
 }
 
+/// Represents the CPU topology presented to the guest
+///
+/// By default, Xen presents every vCPU as a separate single-threaded core, which is rarely
+/// seen on physical hardware. The topology is exposed through CPUID leaf 1 (logical
+/// processors per package and HTT flag) and leaf 4 (cores per package).
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct CpuTopology {
+    /// Number of sockets (physical packages)
+    pub sockets: u8,
+    /// Number of cores per socket
+    pub cores: u8,
+    /// Number of threads per core
+    pub threads: u8,
+}
+
+impl Default for CpuTopology {
+    fn default() -> Self {
+        Self {
+            sockets: 1,
+            cores: 1,
+            threads: 1,
+        }
+    }
+}
+
+impl CpuTopology {
+    /// Get the total number of logical processors of the topology
+    pub fn logical_processors(&self) -> u32 {
+        self.sockets as u32 * self.cores as u32 * self.threads as u32
+    }
+
+    /// Validate the topology against the maximum number of vCPUs of the domain
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if a dimension is zero or if the number of
+    /// logical processors doesn't match the maximum number of vCPUs
+    pub fn validate(
+        &self,
+        maximum_virtual_cpus: &MaximumVirtualCpuNumber,
+    ) -> Result<(), TemplateError> {
+        if self.sockets == 0 || self.cores == 0 || self.threads == 0 {
+            return Err(TemplateError::Validation(format!(
+                "CPU topology {} can't have a zero dimension",
+                self
+            )));
+        }
+
+        if self.logical_processors() != maximum_virtual_cpus.0 as u32 {
+            return Err(TemplateError::Validation(format!(
+                "CPU topology {} has {} logical processors, but maxvcpus is {}",
+                self,
+                self.logical_processors(),
+                maximum_virtual_cpus.0
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Get the CPUID leaf policies exposing the topology
+    ///
+    /// # Returns
+    ///
+    /// The leaf policies in the `Xend` format
+    pub fn cpuid_policies(&self) -> Vec<String> {
+        let logical_processors_per_package = self.cores as u32 * self.threads as u32;
+        let hyper_threading = (logical_processors_per_package > 1) as u32;
+
+        vec![
+            // EBX[23:16]: maximum number of addressable logical processors per package
+            // EDX[28]: HTT, set when there is more than one logical processor per package
+            format!(
+                "1:ebx={},edx={}",
+                cpuid_register_policy(logical_processors_per_package << 16, 0x00ff_0000),
+                cpuid_register_policy(hyper_threading << 28, 1 << 28)
+            ),
+            // EAX[31:26]: maximum number of addressable cores per package, minus one
+            format!(
+                "4,0:eax={}",
+                cpuid_register_policy((self.cores as u32 - 1) << 26, 0xfc00_0000)
+            ),
+        ]
+    }
+}
+
+impl Display for CpuTopology {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}x{}", self.sockets, self.cores, self.threads)
+    }
+}
+
+impl XlConfiguration for CpuTopology {
+    // cpuid=[ "LEAF:REG=POLICY,...", ... ]
+    fn xl_config(&self) -> String {
+        cpuid_xl_config(&self.cpuid_policies())
+    }
+}
+
+/// Represents the CPU model presented to the guest
+///
+/// The vendor is exposed through CPUID leaf 0 and the brand string through the extended
+/// leaves `0x80000002` to `0x80000004`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct CpuModel {
+    /// Vendor identification string, e.g. `GenuineIntel`. Host vendor is used when `None`.
+    pub vendor: Option<String>,
+    /// Processor brand string, e.g. `Intel(R) Core(TM) i7-8700 CPU @ 3.20GHz`. Host brand
+    /// is used when `None`.
+    pub brand: Option<String>,
+    /// Hypervisor vendor signature exposed through CPUID leaf `0x40000000`, e.g.
+    /// `Microsoft Hv`, padded with NUL characters. Xen signature is used when `None`.
+    ///
+    /// ⚠️ This has no effect with libxl, which generates the hypervisor leaves itself, so it is
+    /// rejected by [`CpuModel::validate_dialect`] in every dialect.
+    pub hypervisor_vendor: Option<String>,
+}
+
+impl CpuModel {
+    /// Length of the vendor identification string in bytes
+    pub const VENDOR_LENGTH: usize = 12;
+    /// Maximum length of the processor brand string in bytes
+    pub const MAXIMUM_BRAND_LENGTH: usize = 48;
+    /// First hypervisor CPUID leaf, holding the hypervisor vendor signature
+    pub const HYPERVISOR_LEAF: u32 = 0x4000_0000;
+
+    /// Validate the CPU model
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if the vendor is not 12 ASCII characters or
+    /// if the brand or the hypervisor vendor are not ASCII or longer than 48 and 12 bytes
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        if let Some(vendor) = &self.vendor {
+            if !vendor.is_ascii() || vendor.len() != Self::VENDOR_LENGTH {
+                return Err(TemplateError::Validation(format!(
+                    "CPU vendor \"{}\" must be {} ASCII characters",
+                    vendor,
+                    Self::VENDOR_LENGTH
+                )));
+            }
+        }
+
+        if let Some(brand) = &self.brand {
+            if !brand.is_ascii() || brand.len() > Self::MAXIMUM_BRAND_LENGTH {
+                return Err(TemplateError::Validation(format!(
+                    "CPU brand \"{}\" must be at most {} ASCII characters",
+                    brand,
+                    Self::MAXIMUM_BRAND_LENGTH
+                )));
+            }
+        }
+
+        if let Some(hypervisor_vendor) = &self.hypervisor_vendor {
+            if !hypervisor_vendor.is_ascii() || hypervisor_vendor.len() > Self::VENDOR_LENGTH {
+                return Err(TemplateError::Validation(format!(
+                    "hypervisor vendor \"{}\" must be at most {} ASCII characters",
+                    hypervisor_vendor,
+                    Self::VENDOR_LENGTH
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the CPU model can be rendered in a dialect
+    ///
+    /// # Arguments
+    ///
+    /// * `dialect` - The dialect of the rendered configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if a hypervisor vendor is set, as no toolstack
+    /// applies the hypervisor leaves and it would be silently ignored
+    pub fn validate_dialect(&self, dialect: XlDialect) -> Result<(), TemplateError> {
+        if self.hypervisor_vendor.is_some() {
+            return Err(TemplateError::Validation(format!(
+                "the hypervisor vendor can't be applied in the {} dialect, the toolstack generates the hypervisor leaves",
+                dialect
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Get the CPUID leaf policies exposing the CPU model
+    ///
+    /// # Returns
+    ///
+    /// The leaf policies in the `Xend` format
+    pub fn cpuid_policies(&self) -> Vec<String> {
+        let mut policies = Vec::new();
+
+        if let Some(vendor) = &self.vendor {
+            // The vendor string is stored in EBX, EDX and ECX, in that order
+            let registers = Self::registers(vendor.as_bytes(), Self::VENDOR_LENGTH);
+            policies.push(format!(
+                "0:ebx={},ecx={},edx={}",
+                cpuid_register_policy(registers[0], u32::MAX),
+                cpuid_register_policy(registers[2], u32::MAX),
+                cpuid_register_policy(registers[1], u32::MAX)
+            ));
+        }
+
+        if let Some(brand) = &self.brand {
+            // The brand string is stored in EAX, EBX, ECX and EDX of three leaves, padded
+            // with NUL characters
+            let registers = Self::registers(brand.as_bytes(), Self::MAXIMUM_BRAND_LENGTH);
+            for (leaf, registers) in (0x8000_0002u32..).zip(registers.chunks(4)) {
+                policies.push(format!(
+                    "{:#x}:eax={},ebx={},ecx={},edx={}",
+                    leaf,
+                    cpuid_register_policy(registers[0], u32::MAX),
+                    cpuid_register_policy(registers[1], u32::MAX),
+                    cpuid_register_policy(registers[2], u32::MAX),
+                    cpuid_register_policy(registers[3], u32::MAX)
+                ));
+            }
+        }
+
+        policies
+    }
+
+    /// Get the CPUID leaf policies exposing the hypervisor vendor
+    ///
+    /// # Returns
+    ///
+    /// The leaf policies in the `Xend` format, to be rendered with [`XlDialect::Xend`] only.
+    /// They currently have no effect, see [`CpuModel::validate_dialect`].
+    pub fn hypervisor_cpuid_policies(&self) -> Vec<String> {
+        self.hypervisor_vendor
+            .iter()
+            .map(|hypervisor_vendor| {
+                // The signature is stored in EBX, ECX and EDX, in that order
+                let registers = Self::registers(hypervisor_vendor.as_bytes(), Self::VENDOR_LENGTH);
+                format!(
+                    "{:#x}:ebx={},ecx={},edx={}",
+                    Self::HYPERVISOR_LEAF,
+                    cpuid_register_policy(registers[0], u32::MAX),
+                    cpuid_register_policy(registers[1], u32::MAX),
+                    cpuid_register_policy(registers[2], u32::MAX)
+                )
+            })
+            .collect()
+    }
+
+    /// Split a string into little-endian registers, padded with NUL characters
+    fn registers(bytes: &[u8], length: usize) -> Vec<u32> {
+        let mut padded = bytes.to_vec();
+        padded.resize(length, 0);
+        padded
+            .chunks(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect()
+    }
+}
+
+impl XlConfiguration for CpuModel {
+    // cpuid=[ "LEAF:REG=POLICY,...", ... ]
+    fn xl_config(&self) -> String {
+        cpuid_xl_config(&self.cpuid_policies())
+    }
+}
+
 /// Represents the SMBIOS information for a domain
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct SmBios {
     pub bios_vendor: Option<String>,
     pub bios_version: Option<String>,
@@ -94,9 +437,91 @@ pub struct SmBios {
     pub oems: Option<Vec<String>>,
 }
 
-impl Display for SmBios {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let fields = [
+impl SmBios {
+    /// Characters which can't be used in a SMBIOS value, as they would break the `smbios`
+    /// array of the `xl.cfg`
+    const FORBIDDEN_CHARACTERS: [char; 4] = [',', '"', '\n', '\r'];
+
+    /// Practical size limit of the SMBIOS strings, in bytes. hvmloader builds the SMBIOS
+    /// tables of the guest in a fixed-size area, strings which don't fit are silently dropped.
+    pub const MAXIMUM_SIZE: usize = 4096;
+
+    /// Path where the SMBIOS strings of the host are exposed by sysfs
+    pub const SYSFS_DMI_PATH: &str = "/sys/class/dmi/id";
+
+    /// Read the SMBIOS strings of the host, so that the guest mimics the physical machine
+    ///
+    /// Only Linux is supported, see [`SmBios::from_host_in`] for the details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`std::io::Error`] if the DMI directory can't be read, or of kind
+    /// [`std::io::ErrorKind::Unsupported`] on other platforms
+    pub fn from_host() -> Result<SmBios, std::io::Error> {
+        #[cfg(target_os = "linux")]
+        {
+            SmBios::from_host_in(Path::new(SmBios::SYSFS_DMI_PATH))
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "the host SMBIOS strings can only be read on Linux",
+            ))
+        }
+    }
+
+    /// Read the SMBIOS strings exposed in a sysfs DMI directory
+    ///
+    /// Fields which can't be read, e.g. serial numbers only readable by root, or which are
+    /// empty are left to `None`. The characters rejected by [`SmBios::validate`], e.g. the comma
+    /// of "Dell Inc., Ltd.", are removed so that the strings can be rendered. The location in
+    /// chassis, the battery and the OEM strings are not exposed by sysfs.
+    ///
+    /// # Arguments
+    ///
+    /// * `sysfs_dmi_path` - The DMI directory, e.g. [`SmBios::SYSFS_DMI_PATH`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`std::io::Error`] if the directory can't be read
+    pub fn from_host_in(sysfs_dmi_path: &Path) -> Result<SmBios, std::io::Error> {
+        std::fs::read_dir(sysfs_dmi_path)?;
+
+        let field = |name: &str| {
+            std::fs::read_to_string(sysfs_dmi_path.join(name))
+                .ok()
+                .map(|value| {
+                    value
+                        .replace(SmBios::FORBIDDEN_CHARACTERS, "")
+                        .trim()
+                        .to_string()
+                })
+                .filter(|value| !value.is_empty())
+        };
+
+        Ok(SmBios {
+            bios_vendor: field("bios_vendor"),
+            bios_version: field("bios_version"),
+            system_manufacturer: field("sys_vendor"),
+            system_product_name: field("product_name"),
+            system_version: field("product_version"),
+            system_serial_number: field("product_serial"),
+            baseboard_manufacturer: field("board_vendor"),
+            baseboard_product_name: field("board_name"),
+            baseboard_version: field("board_version"),
+            baseboard_serial_number: field("board_serial"),
+            baseboard_asset_tag: field("board_asset_tag"),
+            enclosure_manufacturer: field("chassis_vendor"),
+            enclosure_serial_number: field("chassis_serial"),
+            enclosure_asset_tag: field("chassis_asset_tag"),
+            ..Default::default()
+        })
+    }
+
+    /// Get the SMBIOS fields, in the order of the `xl.cfg` specification
+    fn fields(&self) -> [(&'static str, &Option<String>); 17] {
+        [
             ("bios_vendor", &self.bios_vendor),
             ("bios_version", &self.bios_version),
             ("system_manufacturer", &self.system_manufacturer),
@@ -117,7 +542,63 @@ impl Display for SmBios {
             ("enclosure_asset_tag", &self.enclosure_asset_tag),
             ("battery_manufacturer", &self.battery_manufacturer),
             ("battery_device_name", &self.battery_device_name),
-        ];
+        ]
+    }
+
+    /// Get the size of the SMBIOS strings, as stored in the guest tables
+    ///
+    /// # Returns
+    ///
+    /// The size of the set values and OEM strings in bytes, including their NUL terminator
+    pub fn byte_size(&self) -> usize {
+        let fields = self
+            .fields()
+            .into_iter()
+            .filter_map(|(_, value)| value.as_ref().map(String::len));
+        let oems = self.oems.iter().flatten().map(String::len);
+
+        fields.chain(oems).map(|length| length + 1).sum()
+    }
+
+    /// Validate the SMBIOS values
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] if a value contains a comma, a double quote or a
+    /// line break, which would produce a malformed `smbios` array, or if the strings exceed
+    /// [`SmBios::MAXIMUM_SIZE`]
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        let byte_size = self.byte_size();
+        if byte_size > SmBios::MAXIMUM_SIZE {
+            return Err(TemplateError::Validation(format!(
+                "SMBIOS strings take {} bytes, more than the {} bytes limit",
+                byte_size,
+                SmBios::MAXIMUM_SIZE
+            )));
+        }
+
+        let fields = self
+            .fields()
+            .into_iter()
+            .filter_map(|(name, value)| value.as_deref().map(|value| (name, value)));
+        let oems = self.oems.iter().flatten().map(|oem| ("oem", oem.as_str()));
+
+        for (name, value) in fields.chain(oems) {
+            if value.contains(SmBios::FORBIDDEN_CHARACTERS) {
+                return Err(TemplateError::Validation(format!(
+                    "SMBIOS {} value {:?} can't contain commas, double quotes or line breaks",
+                    name, value
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for SmBios {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = self.fields();
 
         // oem is a special case, as it is a list of "oem=value" pairs
         // for example, if oems = ["Xenith", "Xenith VM"], then the string
@@ -241,4 +722,293 @@ mod tests {
             "smbios = [ \"bios_vendor=Xenith\", \"bios_version=1.0\", \"system_manufacturer=Xenith\", \"system_product_name=Xenith VM\", \"system_version=1.0\", \"system_serial_number=123\", \"baseboard_manufacturer=Xenith\", \"baseboard_product_name=Xenith VM\", \"baseboard_version=1.0\", \"baseboard_serial_number=123\", \"baseboard_asset_tag=123\", \"baseboard_location_in_chassis=123\", \"enclosure_manufacturer=Xenith\", \"enclosure_serial_number=123\", \"enclosure_asset_tag=123\", \"battery_manufacturer=Xenith\", \"battery_device_name=Xenith VM\", \"oem=Xenith\", \"oem=Xenith VM\" ]"
         );
     }
+
+    #[test]
+    fn test_cpuid_register_policy() {
+        assert_eq!(
+            cpuid_register_policy(0, 0),
+            "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
+        );
+        assert_eq!(
+            cpuid_register_policy(0x0008_0000, 0x00ff_0000),
+            "xxxxxxxx00001000xxxxxxxxxxxxxxxx"
+        );
+        assert_eq!(
+            cpuid_register_policy(u32::MAX, 1),
+            "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx1"
+        );
+    }
+
+    #[test]
+    fn test_cpu_topology_display() {
+        let topology = CpuTopology {
+            sockets: 1,
+            cores: 4,
+            threads: 2,
+        };
+        assert_eq!(topology.to_string(), "1x4x2");
+        assert_eq!(topology.logical_processors(), 8);
+    }
+
+    #[test]
+    fn test_cpu_topology_xl_config() {
+        let topology = CpuTopology {
+            sockets: 1,
+            cores: 4,
+            threads: 2,
+        };
+        assert_eq!(
+            topology.xl_config(),
+            "cpuid = [ \"1:ebx=xxxxxxxx00001000xxxxxxxxxxxxxxxx,edx=xxx1xxxxxxxxxxxxxxxxxxxxxxxxxxxx\", \"4,0:eax=000011xxxxxxxxxxxxxxxxxxxxxxxxxx\" ]"
+        );
+    }
+
+    #[test]
+    fn test_cpu_topology_validate() {
+        let topology = CpuTopology {
+            sockets: 1,
+            cores: 4,
+            threads: 2,
+        };
+        assert!(topology.validate(&MaximumVirtualCpuNumber(8)).is_ok());
+        assert!(matches!(
+            topology.validate(&MaximumVirtualCpuNumber(4)),
+            Err(TemplateError::Validation(e)) if e == "CPU topology 1x4x2 has 8 logical processors, but maxvcpus is 4"
+        ));
+
+        let topology = CpuTopology {
+            sockets: 1,
+            cores: 0,
+            threads: 2,
+        };
+        assert!(topology.validate(&MaximumVirtualCpuNumber(0)).is_err());
+    }
+
+    #[test]
+    fn test_cpu_model_xl_config() {
+        let cpu_model = CpuModel {
+            vendor: Some("GenuineIntel".to_string()),
+            brand: None,
+            hypervisor_vendor: None,
+        };
+        // "Genu" "ineI" "ntel" as little-endian registers
+        assert_eq!(
+            cpu_model.xl_config(),
+            format!(
+                "cpuid = [ \"0:ebx={:032b},ecx={:032b},edx={:032b}\" ]",
+                0x756e_6547u32, 0x6c65_746eu32, 0x4965_6e69u32
+            )
+        );
+
+        let cpu_model = CpuModel {
+            vendor: None,
+            brand: Some("Intel(R) Core(TM) i7-8700 CPU @ 3.20GHz".to_string()),
+            hypervisor_vendor: None,
+        };
+        let policies = cpu_model.cpuid_policies();
+        assert_eq!(policies.len(), 3);
+        assert!(policies[0].starts_with(&format!("0x80000002:eax={:032b},", 0x6574_6e49u32)));
+        assert!(policies[2].starts_with("0x80000004:eax="));
+        // Brand string is padded with NUL characters
+        assert!(policies[2].ends_with(&format!("edx={:032b}", 0)));
+
+        assert_eq!(CpuModel::default().xl_config(), "");
+    }
+
+    #[test]
+    fn test_cpu_model_validate() {
+        let cpu_model = CpuModel {
+            vendor: Some("GenuineIntel".to_string()),
+            brand: Some("A".repeat(CpuModel::MAXIMUM_BRAND_LENGTH)),
+            hypervisor_vendor: Some("Microsoft Hv".to_string()),
+        };
+        assert!(cpu_model.validate().is_ok());
+
+        let cpu_model = CpuModel {
+            vendor: None,
+            brand: Some("A".repeat(CpuModel::MAXIMUM_BRAND_LENGTH + 1)),
+            hypervisor_vendor: None,
+        };
+        assert!(matches!(
+            cpu_model.validate(),
+            Err(TemplateError::Validation(_))
+        ));
+
+        let cpu_model = CpuModel {
+            vendor: Some("Intel".to_string()),
+            brand: None,
+            hypervisor_vendor: None,
+        };
+        assert!(matches!(
+            cpu_model.validate(),
+            Err(TemplateError::Validation(_))
+        ));
+
+        let cpu_model = CpuModel {
+            vendor: None,
+            brand: None,
+            hypervisor_vendor: Some("MicrosoftHv-1".to_string()),
+        };
+        assert!(matches!(
+            cpu_model.validate(),
+            Err(TemplateError::Validation(e)) if e == "hypervisor vendor \"MicrosoftHv-1\" must be at most 12 ASCII characters"
+        ));
+    }
+
+    #[test]
+    fn test_cpu_model_hypervisor_cpuid_policies() {
+        let cpu_model = CpuModel {
+            vendor: None,
+            brand: None,
+            hypervisor_vendor: Some("KVMKVMKVM".to_string()),
+        };
+        // "KVMK" "VMKV" "M" as little-endian registers, padded with NUL characters
+        assert_eq!(
+            cpu_model.hypervisor_cpuid_policies(),
+            vec![format!(
+                "0x40000000:ebx={:032b},ecx={:032b},edx={:032b}",
+                0x4b4d_564bu32, 0x564b_4d56u32, 0x0000_004du32
+            )]
+        );
+        // The hypervisor leaf is not part of the Libxl policies
+        assert_eq!(cpu_model.xl_config(), "");
+
+        assert!(matches!(
+            cpu_model.validate_dialect(XlDialect::Libxl),
+            Err(TemplateError::Validation(e)) if e == "the hypervisor vendor can't be applied in the libxl dialect, the toolstack generates the hypervisor leaves"
+        ));
+        assert!(matches!(
+            cpu_model.validate_dialect(XlDialect::Xend),
+            Err(TemplateError::Validation(_))
+        ));
+        assert!(CpuModel::default().hypervisor_cpuid_policies().is_empty());
+        assert!(
+            CpuModel::default()
+                .validate_dialect(XlDialect::Libxl)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_alternate_p2m_mode_serde_round_trip() {
+        for mode in [
+            AlternateP2mMode::Disabled,
+            AlternateP2mMode::Mixed,
+            AlternateP2mMode::External,
+            AlternateP2mMode::Limited,
+        ] {
+            let serialized = serde_json::to_string(&mode).unwrap();
+            assert_eq!(
+                serde_json::from_str::<AlternateP2mMode>(&serialized).unwrap(),
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn test_smbios_byte_size() {
+        let mut smbios = SmBios {
+            bios_vendor: Some("Xenith".to_string()),
+            oems: Some(vec!["Xen".to_string(), "".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(smbios.byte_size(), 7 + 4 + 1);
+        assert!(smbios.validate().is_ok());
+        assert_eq!(SmBios::default().byte_size(), 0);
+
+        // Many OEM strings exceed the limit
+        smbios.oems = Some(vec!["A".repeat(63); 65]);
+        assert!(smbios.byte_size() > SmBios::MAXIMUM_SIZE);
+        assert!(matches!(
+            smbios.validate(),
+            Err(TemplateError::Validation(e)) if e.contains("more than the 4096 bytes limit")
+        ));
+    }
+
+    #[test]
+    fn test_smbios_from_host_in() {
+        let dmi = tempfile::tempdir().unwrap();
+        for (name, value) in [
+            ("bios_vendor", "American Megatrends Inc.\n"),
+            ("sys_vendor", "Dell Inc.\n"),
+            ("product_name", "OptiPlex 7070\n"),
+            ("board_name", "0YNVJG\n"),
+            // Unset by the firmware
+            ("chassis_asset_tag", "\n"),
+        ] {
+            std::fs::write(dmi.path().join(name), value).unwrap();
+        }
+        // Unreadable fields are ignored, e.g. serial numbers only readable by root
+        std::fs::create_dir(dmi.path().join("product_serial")).unwrap();
+
+        let smbios = SmBios::from_host_in(dmi.path()).unwrap();
+        assert_eq!(
+            smbios,
+            SmBios {
+                bios_vendor: Some("American Megatrends Inc.".to_string()),
+                system_manufacturer: Some("Dell Inc.".to_string()),
+                system_product_name: Some("OptiPlex 7070".to_string()),
+                baseboard_product_name: Some("0YNVJG".to_string()),
+                ..Default::default()
+            }
+        );
+        assert!(smbios.validate().is_ok());
+
+        assert!(SmBios::from_host_in(&dmi.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_smbios_from_host_in_sanitizes_values() {
+        let dmi = tempfile::tempdir().unwrap();
+        for (name, value) in [
+            ("sys_vendor", "Dell Inc., Ltd.\n"),
+            ("product_name", "\"OptiPlex\" 7070\n"),
+            // Only made of rejected characters
+            ("board_vendor", ",\n"),
+        ] {
+            std::fs::write(dmi.path().join(name), value).unwrap();
+        }
+
+        let smbios = SmBios::from_host_in(dmi.path()).unwrap();
+        assert_eq!(
+            smbios,
+            SmBios {
+                system_manufacturer: Some("Dell Inc. Ltd.".to_string()),
+                system_product_name: Some("OptiPlex 7070".to_string()),
+                ..Default::default()
+            }
+        );
+        assert!(smbios.validate().is_ok());
+    }
+
+    #[test]
+    fn test_smbios_validate() {
+        let smbios = SmBios {
+            system_manufacturer: Some("Dell Inc.".to_string()),
+            oems: Some(vec!["Xenith".to_string()]),
+            ..Default::default()
+        };
+        assert!(smbios.validate().is_ok());
+
+        let smbios = SmBios {
+            system_manufacturer: Some("Acme, Inc.".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            smbios.validate(),
+            Err(TemplateError::Validation(e)) if e == "SMBIOS system_manufacturer value \"Acme, Inc.\" can't contain commas, double quotes or line breaks"
+        ));
+
+        let smbios = SmBios {
+            oems: Some(vec!["Xenith".to_string(), "\"quoted\"".to_string()]),
+            ..Default::default()
+        };
+        assert!(smbios.validate().is_err());
+
+        let smbios = SmBios {
+            bios_version: Some("1.0\n".to_string()),
+            ..Default::default()
+        };
+        assert!(smbios.validate().is_err());
+    }
 }
@@ -18,14 +18,17 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 //! Time configuration structures and options for a domain.
 
+use serde::{Deserialize, Serialize};
+
 use std::fmt::Display;
 
 use crate::XlConfiguration;
+use crate::error::TemplateError;
 
 /// Represents the mode of the Time Stamp Counter (TSC) for a domain
 ///
 /// See `man 7 xen-tscmode` for more information.
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum TimeStampCounterMode {
     /// Guest rdtsc/p is executed natively when monotonicity can be guaranteed and
     /// emulated otherwise (with frequency scaled if necessary).
@@ -47,6 +50,30 @@ pub enum TimeStampCounterMode {
     /// guarantees). Guest rdtsc/p is emulated at native frequency if unsupported by
     /// h/w, else executed natively.
     Native,
+    /// Same as native, except that Xen manages the TSC_AUX register so the guest can
+    /// determine when a restore or migration has occurred (pvrdtscp).
+    ///
+    /// ⚠️ pvrdtscp was removed in Xen 4.10 and libxl refuses this mode, so it is rejected by
+    /// [`TimeStampCounterMode::validate`]. It is kept to read configurations of older hosts.
+    NativeParavirt,
+}
+
+impl TimeStampCounterMode {
+    /// Validate the TSC mode
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError::Validation`] for [`TimeStampCounterMode::NativeParavirt`],
+    /// which `xl create` rejects since Xen 4.10
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        if *self == TimeStampCounterMode::NativeParavirt {
+            return Err(TemplateError::Validation(
+                "the native_paravirt TSC mode was removed in Xen 4.10".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 impl Display for TimeStampCounterMode {
@@ -55,6 +82,7 @@ impl Display for TimeStampCounterMode {
             TimeStampCounterMode::Default => write!(f, "default"),
             TimeStampCounterMode::AlwaysEmulate => write!(f, "always_emulate"),
             TimeStampCounterMode::Native => write!(f, "native"),
+            TimeStampCounterMode::NativeParavirt => write!(f, "native_paravirt"),
         }
     }
 }
@@ -65,6 +93,32 @@ impl XlConfiguration for TimeStampCounterMode {
     }
 }
 
+/// Represents the offset of the emulated real time clock (RTC) of a domain
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum ClockOffset {
+    /// The RTC is set to UTC, as expected by Linux guests.
+    #[default]
+    Utc,
+    /// The RTC is set to the local time of the host, as expected by Windows guests.
+    LocalTime,
+}
+
+impl Display for ClockOffset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClockOffset::Utc => write!(f, "utc"),
+            ClockOffset::LocalTime => write!(f, "localtime"),
+        }
+    }
+}
+
+impl XlConfiguration for ClockOffset {
+    // localtime=BOOLEAN
+    fn xl_config(&self) -> String {
+        format!("localtime = {}", (*self == ClockOffset::LocalTime) as u8)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -77,6 +131,10 @@ mod tests {
             "always_emulate"
         );
         assert_eq!(format!("{}", TimeStampCounterMode::Native), "native");
+        assert_eq!(
+            format!("{}", TimeStampCounterMode::NativeParavirt),
+            "native_paravirt"
+        );
     }
 
     #[test]
@@ -93,5 +151,42 @@ mod tests {
             TimeStampCounterMode::Native.xl_config(),
             "tsc_mode = \"native\""
         );
+        assert_eq!(
+            TimeStampCounterMode::NativeParavirt.xl_config(),
+            "tsc_mode = \"native_paravirt\""
+        );
+    }
+
+    #[test]
+    fn test_tsc_mode_validate() {
+        assert!(TimeStampCounterMode::Native.validate().is_ok());
+        assert!(matches!(
+            TimeStampCounterMode::NativeParavirt.validate(),
+            Err(TemplateError::Validation(e)) if e == "the native_paravirt TSC mode was removed in Xen 4.10"
+        ));
+    }
+
+    #[test]
+    fn test_clock_offset_xl_config() {
+        assert_eq!(ClockOffset::Utc.to_string(), "utc");
+        assert_eq!(ClockOffset::LocalTime.to_string(), "localtime");
+        assert_eq!(ClockOffset::Utc.xl_config(), "localtime = 0");
+        assert_eq!(ClockOffset::LocalTime.xl_config(), "localtime = 1");
+    }
+
+    #[test]
+    fn test_time_stamp_counter_mode_serde_round_trip() {
+        for tsc_mode in [
+            TimeStampCounterMode::Default,
+            TimeStampCounterMode::AlwaysEmulate,
+            TimeStampCounterMode::Native,
+            TimeStampCounterMode::NativeParavirt,
+        ] {
+            let serialized = serde_json::to_string(&tsc_mode).unwrap();
+            assert_eq!(
+                serde_json::from_str::<TimeStampCounterMode>(&serialized).unwrap(),
+                tsc_mode
+            );
+        }
     }
 }
@@ -1,3 +1,70 @@
-// use thiserror::Error;
+/*
+Xenith - Xen-based security hypervisor
+Copyright (C) 2025 Xenith contributors
 
-// Created for future usage
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Error types for the xenith-vm crate
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::domain::DiskFormat;
+
+/// Error type for domain configuration templating
+///
+/// This error type is used to represent errors that can occur when generating a
+/// domain configuration, without leaking the templating backend to consumers.
+#[derive(Error, Debug)]
+pub enum TemplateError {
+    #[error("Template rendering failed: {0}")]
+    Tera(#[from] tera::Error),
+    #[error("Invalid domain configuration: {0}")]
+    Validation(String),
+    #[error("Invalid network configuration: {0}")]
+    Network(#[from] NetworkError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Error type for network configuration
+///
+/// This error type is used to represent errors that can occur when checking the network
+/// configuration of a domain against the host.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum NetworkError {
+    #[error("Bridge {0} does not exist")]
+    BridgeNotFound(String),
+    #[error("Interface {0} is not a bridge")]
+    NotABridge(String),
+    #[error("Failed to list network interfaces: {0}")]
+    InterfaceListing(String),
+}
+
+/// Error type for disk operations
+///
+/// This error type is used to represent errors that can occur when manipulating disk images.
+#[derive(Error, Debug)]
+pub enum DiskError {
+    #[error("Disk format {0} does not support snapshots")]
+    SnapshotUnsupported(DiskFormat),
+    #[error("Snapshot {0} already exists")]
+    SnapshotAlreadyExists(PathBuf),
+    #[error("qemu-img failed: {0}")]
+    QemuImg(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
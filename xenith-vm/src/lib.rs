@@ -51,13 +51,45 @@ pub mod error;
 pub mod templating;
 
 /// Allows for the generation of the xl domain configuration
+///
+/// Every configuration type of the [`domain`] module implements this trait, which turns
+/// the value into its `xl.cfg` representation (see `man xl.cfg`). The outputs of multiple
+/// types can then be composed, line by line, into a full domain configuration file.
+///
+/// # Example
+///
+/// ```
+/// use xenith_vm::XlConfiguration;
+/// use xenith_vm::domain::{DomainName, MemoryCapacity};
+///
+/// /// Custom option which is not modelled by the crate
+/// struct Vga(String);
+///
+/// impl XlConfiguration for Vga {
+///     fn xl_config(&self) -> String {
+///         format!("vga = \"{}\"", self.0)
+///     }
+/// }
+///
+/// let options: Vec<Box<dyn XlConfiguration>> = vec![
+///     Box::new(DomainName("xenith".to_string())),
+///     Box::new(MemoryCapacity(2048)),
+///     Box::new(Vga("stdvga".to_string())),
+/// ];
+///
+/// let config = options
+///     .iter()
+///     .map(|option| option.xl_config())
+///     .collect::<Vec<String>>()
+///     .join("\n");
+///
+/// assert_eq!(config, "name = \"xenith\"\nmemory = 2048\nvga = \"stdvga\"");
+/// ```
 pub trait XlConfiguration {
     /// Generate the xl configuration string for the domain
     ///
     /// # Returns
     ///
     /// A string containing the xl configuration for the domain
-    fn xl_config(&self) -> String {
-        unimplemented!()
-    }
+    fn xl_config(&self) -> String;
 }
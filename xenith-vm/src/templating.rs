@@ -19,13 +19,15 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 //! Xenith domain configuration templating
 
 use crate::XlConfiguration;
-use crate::domain::Domain;
+use crate::domain::{Domain, XlDialect};
+use crate::error::TemplateError;
 
 use tera::{Context, Tera};
 
 /// Domain configuration templating
 ///
-/// This struct is used to generate a domain configuration file from a [`Domain`] object
+/// This struct is used to generate a domain configuration file from a [`Domain`] object, in
+/// the [`XlDialect::Libxl`] dialect unless created with [`DomainTemplate::with_dialect`]
 #[derive(Debug, Clone, Default)]
 pub struct DomainTemplate {
     tera: Tera,
@@ -35,6 +37,43 @@ pub struct DomainTemplate {
 impl DomainTemplate {
     pub const DEFAULT_CONFIG_TEMPLATE: &str = "templates/default-config.cfg";
 
+    /// Prefix of the annotation lines, distinct from the section titles so they can be stripped
+    pub const ANNOTATION_PREFIX: &str = "## ";
+
+    /// Annotation written after each section title by [`DomainTemplate::render_annotated`]
+    const SECTION_ANNOTATIONS: &[(&str, &str)] = &[
+        (
+            "# Generic domain configuration",
+            "Domain name, type (pv, pvh or hvm) and memory allocation",
+        ),
+        (
+            "# Boot",
+            "Guest firmware and boot order (c = hard disk, d = CD-ROM, n = network)",
+        ),
+        (
+            "# Devices",
+            "Virtual disks, emulated disk controllers and other emulated hardware",
+        ),
+        ("# Console", "Remote display of the guest console"),
+        ("# Network", "Virtual network interfaces and their backend"),
+        (
+            "# Events",
+            "Action taken when the domain powers off, reboots, crashes or times out",
+        ),
+        (
+            "# Processor",
+            "Virtual CPUs, CPUID overrides and guest-visible firmware tables",
+        ),
+        (
+            "# Time",
+            "TSC emulation mode (see `man 7 xen-tscmode`) and real time clock offset",
+        ),
+        (
+            "# Extra configuration",
+            "Raw options appended as is, they are not validated by xenith-vm",
+        ),
+    ];
+
     /// Create a new [`Tera`] domain template
     ///
     /// # Arguments
@@ -43,8 +82,37 @@ impl DomainTemplate {
     ///
     /// # Returns
     ///
-    /// A [`Result`] containing the [`DomainTemplate`] if successful, or a [`tera::Error`] if not
-    pub fn new(domain: Domain) -> Result<Self, tera::Error> {
+    /// A [`Result`] containing the [`DomainTemplate`] if successful, or a [`TemplateError`] if not
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError`] if the domain is invalid, see [`Domain::validate`]
+    pub fn new(domain: Domain) -> Result<Self, TemplateError> {
+        DomainTemplate::with_dialect(domain, XlDialect::default())
+    }
+
+    /// Create a new [`Tera`] domain template rendered in a given dialect
+    ///
+    /// # Arguments
+    ///
+    /// * `domain` - The Xenith [`Domain`] to be templated
+    /// * `dialect` - The [`XlDialect`] of the rendered configuration
+    ///
+    /// # Returns
+    ///
+    /// A [`Result`] containing the [`DomainTemplate`] if successful, or a [`TemplateError`] if not
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TemplateError`] if the domain is invalid, see [`Domain::validate`], or if
+    /// it can't be rendered in the dialect, see [`crate::domain::CpuModel::validate_dialect`]
+    pub fn with_dialect(domain: Domain, dialect: XlDialect) -> Result<Self, TemplateError> {
+        // Bridges are not checked, as the configuration may be generated for another host
+        domain.validate(false)?;
+        if let Some(cpu_model) = &domain.cpu_model {
+            cpu_model.validate_dialect(dialect)?;
+        }
+
         let mut tera = Tera::default();
         tera.add_template_file(DomainTemplate::DEFAULT_CONFIG_TEMPLATE, None)?;
 
@@ -56,23 +124,54 @@ impl DomainTemplate {
         context.insert("memory", &domain.memory.xl_config());
         context.insert("maximum_memory", &domain.maximum_memory.xl_config());
         context.insert("nested_hvm", &domain.nested_hvm.xl_config());
+        context.insert(
+            "viridian",
+            &domain.hvm_xl_config(domain.viridian.xl_config()),
+        );
 
         // Boot
-        context.insert("firmware", &domain.firmware.xl_config());
-        context.insert("boot_devices", &domain.boot_devices.xl_config());
+        context.insert("boot", &domain.boot_xl_config());
+        context.insert(
+            "boot_devices",
+            &domain.hvm_xl_config(domain.boot_devices.xl_config()),
+        );
 
         // Devices
         context.insert("disks", &domain.disks.xl_config());
         context.insert(
             "emulated_disk_controller",
-            &domain.emulated_disk_controller.xl_config(),
+            &domain.hvm_xl_config(domain.emulated_disk_controller.xl_config()),
+        );
+        context.insert(
+            "device_model_version",
+            &domain.hvm_xl_config(domain.device_model_version.xl_config()),
+        );
+        context.insert(
+            "sound_hardware",
+            &domain.hvm_xl_config(domain.sound_hardware.xl_config()),
+        );
+        context.insert("video_adapter", &domain.video_adapter_xl_config());
+        context.insert("usb_devices", &domain.usb_devices_xl_config());
+        context.insert("watchdog", &domain.watchdog_xl_config());
+
+        // Console
+        context.insert(
+            "console",
+            &domain
+                .console
+                .as_ref()
+                .map(|console| console.xl_config())
+                .unwrap_or_default(),
         );
 
         // Network
         context.insert("network_interfaces", &domain.network_interfaces.xl_config());
 
         // Events
-        context.insert("domain_actions", &domain.domain_actions.xl_config());
+        context.insert(
+            "domain_actions",
+            &domain.effective_domain_actions().xl_config(),
+        );
 
         // Processor
         context.insert("virtual_cpus", &domain.virtual_cpus.xl_config());
@@ -80,11 +179,16 @@ impl DomainTemplate {
             "maximum_virtual_cpus",
             &domain.maximum_virtual_cpus.xl_config(),
         );
+        context.insert("cpuid", &domain.cpuid_xl_config(dialect));
         context.insert("alternate_p2m", &domain.alternate_p2m.xl_config());
         context.insert("smbios", &domain.smbios.xl_config());
 
         // Time
         context.insert("tsc_mode", &domain.tsc_mode.xl_config());
+        context.insert("clock_offset", &domain.clock_offset.xl_config());
+
+        // Extra configuration
+        context.insert("extra_config", &domain.extra_xl_config());
 
         Ok(Self { tera, context })
     }
@@ -93,28 +197,91 @@ impl DomainTemplate {
     ///
     /// # Returns
     ///
-    /// A [`Result`] containing the rendered domain configuration as a [`String`] if successful, or a [`tera::Error`] if not
-    pub fn render(&self) -> Result<String, tera::Error> {
-        self.tera
-            .render(DomainTemplate::DEFAULT_CONFIG_TEMPLATE, &self.context)
+    /// A [`Result`] containing the rendered domain configuration as a [`String`] if successful, or a [`TemplateError`] if not
+    pub fn render(&self) -> Result<String, TemplateError> {
+        let rendered = self
+            .tera
+            .render(DomainTemplate::DEFAULT_CONFIG_TEMPLATE, &self.context)?;
+        Ok(rendered)
+    }
+
+    /// Render the domain configuration template with annotations
+    ///
+    /// Each section title is followed by a comment line, prefixed by
+    /// [`DomainTemplate::ANNOTATION_PREFIX`], documenting the section for operators editing
+    /// the configuration by hand. Annotations are comments, they are ignored by `xl`.
+    ///
+    /// # Returns
+    ///
+    /// A [`Result`] containing the annotated domain configuration as a [`String`] if successful, or a [`TemplateError`] if not
+    pub fn render_annotated(&self) -> Result<String, TemplateError> {
+        let rendered = self.render()?;
+
+        let mut annotated = String::with_capacity(rendered.len());
+        for line in rendered.split_inclusive('\n') {
+            annotated.push_str(line);
+
+            let annotation = DomainTemplate::SECTION_ANNOTATIONS
+                .iter()
+                .find(|(title, _)| line.trim_end() == *title);
+            if let Some((_, annotation)) = annotation {
+                annotated.push_str(DomainTemplate::ANNOTATION_PREFIX);
+                annotated.push_str(annotation);
+                annotated.push('\n');
+            }
+        }
+
+        Ok(annotated)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::path::PathBuf;
     use std::str::FromStr;
 
     use super::*;
     use crate::domain::*;
 
-    #[test]
-    fn test_domain_template() -> Result<(), tera::Error> {
-        // Create a realist domain configuration
+    /// Parse a configuration with `xl create -n` (dry run)
+    ///
+    /// # Returns
+    ///
+    /// `None` if `xl` is not available, otherwise the error output of `xl` if the
+    /// configuration can't be parsed
+    #[cfg(feature = "xl-check")]
+    fn xl_dry_run(config: &str) -> Option<Result<(), String>> {
+        use std::io::Write;
+        use std::process::Command;
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+
+        let output = match Command::new("xl")
+            .args(["create", "-n"])
+            .arg(file.path())
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => return Some(Err(e.to_string())),
+        };
+
+        if output.status.success() {
+            Some(Ok(()))
+        } else {
+            Some(Err(String::from_utf8_lossy(&output.stderr).to_string()))
+        }
+    }
+
+    /// Create a realist domain configuration
+    fn test_domain() -> Domain {
         let name = DomainName("Xenith".to_string());
         let r#type = DomainType::Hvm;
         let memory = MemoryCapacity(8000); // 8GB
         let maximum_memory = MaximumMemoryCapacity(10000); // 16GB
+        let memory_backing = MemoryBacking::Default;
         let nested_hvm = NestedHvm(true);
         let firmware = Firmware::Uefi;
         let boot_devices = BootDevices(vec![
@@ -129,6 +296,8 @@ mod tests {
                 format: DiskFormat::Qcow2,
                 access: DiskAccess::ReadWrite,
                 virtual_device: "xvda".to_string(),
+                controller: None,
+                backend_domain: None,
             },
             Disk {
                 target: PathBuf::from("/dev/sdb"),
@@ -136,25 +305,39 @@ mod tests {
                 format: DiskFormat::Raw,
                 access: DiskAccess::ReadOnly,
                 virtual_device: "xvdb".to_string(),
+                controller: None,
+                backend_domain: None,
             },
         ]);
         let emulated_disk_controller = EmulatedDiskControllerType::Ahci;
+        let sound_hardware = SoundHardware::Hda;
+        let video_adapter = Some(VideoAdapter {
+            model: VgaModel::Stdvga,
+            memory_mb: Some(32),
+        });
+        let console = Some(GuestConsole::Vnc);
         let network_interfaces = NetworkInterfaces(vec![
             NetworkInterface {
                 name: "vif0.0".to_string(),
                 mac: MacAddress::from_str("00:16:3e:00:00:00").unwrap(),
                 bridge: "xenbr0".to_string(),
-                gateway_device: "eth0".to_string(),
+                gateway_device: String::new(),
+                ip_addresses: Vec::new(),
+                mode: NetworkMode::Bridge,
                 r#type: NetworkInterfaceType::IoEmu,
                 model: Some(NetworkInterfaceModel::Rtl8139),
+                backend_domain: None,
             },
             NetworkInterface {
                 name: "vif0.1".to_string(),
                 mac: MacAddress::from_str("00:16:3e:00:00:01").unwrap(),
                 bridge: "xenbr0".to_string(),
-                gateway_device: "eth0".to_string(),
+                gateway_device: String::new(),
+                ip_addresses: Vec::new(),
+                mode: NetworkMode::Bridge,
                 r#type: NetworkInterfaceType::IoEmu,
                 model: Some(NetworkInterfaceModel::Rtl8139),
+                backend_domain: None,
             },
         ]);
         let domain_actions = DomainActions {
@@ -166,6 +349,11 @@ mod tests {
         };
         let virtual_cpus = VirtualCpuNumber(4);
         let maximum_virtual_cpus = MaximumVirtualCpuNumber(8);
+        let cpu_topology = Some(CpuTopology {
+            sockets: 1,
+            cores: 4,
+            threads: 2,
+        });
         let alternate_p2m = AlternateP2mMode::Mixed;
         let smbios = SmBios {
             bios_vendor: Some("Bios Vendor".to_string()),
@@ -189,24 +377,43 @@ mod tests {
         };
         let tsc_mode = TimeStampCounterMode::Native;
 
-        let domain = Domain {
+        Domain {
             name,
             r#type,
             memory,
             maximum_memory,
+            memory_backing,
             nested_hvm,
+            viridian: Viridian(false),
             firmware,
+            direct_kernel_boot: None,
             boot_devices,
             disks,
             emulated_disk_controller,
+            device_model_version: DeviceModelVersion::QemuXen,
+            sound_hardware,
+            video_adapter,
+            console,
+            usb_tablet: None,
             network_interfaces,
             domain_actions,
+            watchdog: None,
             virtual_cpus,
             maximum_virtual_cpus,
+            cpu_topology,
+            cpu_model: None,
             alternate_p2m,
             smbios,
             tsc_mode,
-        };
+            clock_offset: ClockOffset::Utc,
+            extra_config: Vec::new(),
+            labels: BTreeMap::from([("owner".to_string(), "xenith".to_string())]),
+        }
+    }
+
+    #[test]
+    fn test_domain_template() -> Result<(), TemplateError> {
+        let domain = test_domain();
 
         // Create a new domain template and render it
         let template = DomainTemplate::new(domain)?;
@@ -222,4 +429,202 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_domain_template_annotated() -> Result<(), TemplateError> {
+        let template = DomainTemplate::new(test_domain())?;
+        let rendered = template.render()?;
+        let annotated = template.render_annotated()?;
+
+        assert!(annotated.contains(
+            "# Devices\n## Virtual disks, emulated disk controllers and other emulated hardware\n"
+        ));
+        assert!(annotated.contains("# Processor\n## "));
+
+        // Stripping the annotations yields the plain render
+        let stripped = annotated
+            .split_inclusive('\n')
+            .filter(|line| !line.starts_with(DomainTemplate::ANNOTATION_PREFIX))
+            .collect::<String>();
+        assert_eq!(stripped, rendered);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_domain_xl_config_matches_template() -> Result<(), TemplateError> {
+        let domain = test_domain();
+        let rendered = DomainTemplate::new(domain.clone())?.render()?;
+
+        assert_eq!(domain.xl_config(), rendered);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "xl-check")]
+    fn test_domain_template_xl_dry_run() -> Result<(), TemplateError> {
+        let rendered = DomainTemplate::new(test_domain())?.render()?;
+
+        match xl_dry_run(&rendered) {
+            Some(result) => assert_eq!(result, Ok(())),
+            None => eprintln!("xl is not available, skipping the dry run"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_domain_xl_config_matches_template_without_optional_options() -> Result<(), TemplateError>
+    {
+        let domain = Domain::default();
+        let rendered = DomainTemplate::new(domain.clone())?.render()?;
+
+        assert!(!rendered.contains("hugepages"));
+        assert!(!rendered.contains("soundhw"));
+        assert!(!rendered.contains("vga"));
+        assert!(!rendered.contains("cpuid"));
+        assert!(!rendered.contains("usbdevice"));
+        assert!(!rendered.contains("# Console"));
+        assert_eq!(domain.xl_config(), rendered);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(feature = "pv-support"))]
+    fn test_domain_template_pv_unsupported() {
+        let mut domain = test_domain();
+        domain.r#type = DomainType::Pvh;
+        assert!(matches!(
+            DomainTemplate::new(domain),
+            Err(TemplateError::Validation(e)) if e == "pvh domains are not supported, enable the pv-support feature"
+        ));
+
+        let mut domain = test_domain();
+        domain.direct_kernel_boot = Some(DirectKernelBoot {
+            kernel: PathBuf::from("/boot/vmlinuz"),
+            ..Default::default()
+        });
+        assert!(matches!(
+            DomainTemplate::new(domain),
+            Err(TemplateError::Validation(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "pv-support")]
+    fn test_domain_template_pv() -> Result<(), TemplateError> {
+        let mut domain = Domain {
+            name: DomainName("pv-guest".to_string()),
+            r#type: DomainType::Pv,
+            ..Default::default()
+        };
+        assert!(matches!(
+            DomainTemplate::new(domain.clone()),
+            Err(TemplateError::Validation(e)) if e == "pv domains require a direct kernel boot"
+        ));
+
+        domain.direct_kernel_boot = Some(DirectKernelBoot {
+            kernel: PathBuf::from("/boot/vmlinuz"),
+            ramdisk: Some(PathBuf::from("/boot/initrd.img")),
+            command_line: Some("root=/dev/xvda1 ro".to_string()),
+        });
+        let rendered = DomainTemplate::new(domain.clone())?.render()?;
+
+        assert!(rendered.contains("type = \"pv\"\n"));
+        assert!(rendered.contains(
+            "# Boot\nkernel = \"/boot/vmlinuz\"\nramdisk = \"/boot/initrd.img\"\ncmdline = \"root=/dev/xvda1 ro\"\n\n"
+        ));
+        assert!(!rendered.contains("firmware"));
+        assert!(!rendered.contains("hdtype"));
+        assert_eq!(domain.xl_config(), rendered);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_domain_template_dialect() -> Result<(), TemplateError> {
+        let mut domain = test_domain();
+        domain.cpu_model = Some(CpuModel {
+            vendor: Some("GenuineIntel".to_string()),
+            brand: None,
+            hypervisor_vendor: None,
+        });
+        let libxl = DomainTemplate::new(domain.clone())?.render()?;
+        assert!(libxl.contains("cpuid = [ \"0:ebx="));
+        assert_eq!(
+            DomainTemplate::with_dialect(domain.clone(), XlDialect::Xend)?.render()?,
+            libxl
+        );
+
+        // The hypervisor leaf is not applied by any toolstack
+        domain.cpu_model = Some(CpuModel {
+            vendor: Some("GenuineIntel".to_string()),
+            brand: None,
+            hypervisor_vendor: Some("Microsoft Hv".to_string()),
+        });
+        assert!(matches!(
+            DomainTemplate::new(domain.clone()),
+            Err(TemplateError::Validation(e)) if e == "the hypervisor vendor can't be applied in the libxl dialect, the toolstack generates the hypervisor leaves"
+        ));
+        assert!(matches!(
+            DomainTemplate::with_dialect(domain, XlDialect::Xend),
+            Err(TemplateError::Validation(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_domain_template_disk_collision() {
+        let mut domain = test_domain();
+        domain.disks.0[1].virtual_device = "sda".to_string();
+
+        assert!(matches!(
+            DomainTemplate::new(domain),
+            Err(TemplateError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_domain_template_extra_config() -> Result<(), TemplateError> {
+        let mut domain = test_domain();
+        domain.extra_config = vec![
+            "vga = \"stdvga\"".to_string(),
+            "   ".to_string(),
+            "videoram = 16".to_string(),
+        ];
+
+        let rendered = DomainTemplate::new(domain.clone())?.render()?;
+
+        assert!(rendered.ends_with("# Extra configuration\nvga = \"stdvga\"\nvideoram = 16\n"));
+        assert_eq!(domain.xl_config(), rendered);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_domain_template_watchdog() -> Result<(), TemplateError> {
+        let mut domain = test_domain();
+        domain.watchdog = Some(Watchdog {
+            model: WatchdogModel::Xen,
+            action: EventAction::CoreDumpRestart,
+        });
+
+        let rendered = DomainTemplate::new(domain.clone())?.render()?;
+        assert!(rendered.contains("on_watchdog = \"coredump-restart\""));
+        assert_eq!(domain.xl_config(), rendered);
+
+        domain.watchdog = Some(Watchdog {
+            model: WatchdogModel::Ib700,
+            action: EventAction::CoreDumpRestart,
+        });
+        assert!(matches!(
+            DomainTemplate::new(domain),
+            Err(TemplateError::Validation(_))
+        ));
+
+        Ok(())
+    }
 }